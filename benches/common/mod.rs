@@ -0,0 +1,39 @@
+//! Helpers shared by the benchmarks
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Return the path stored in environment variable `var`, if any
+pub fn env_path(var: &str) -> Option<PathBuf> {
+    env::var_os(var).map(PathBuf::from)
+}
+
+/// Load a capture made of raw little endian 32bit words. Panics if
+/// the file can't be read since there's no point in running the
+/// benchmark with the wrong input.
+pub fn load_words(path: &Path) -> Vec<u32> {
+    let mut data = Vec::new();
+
+    let r = File::open(path).and_then(|mut f| f.read_to_end(&mut data));
+
+    if let Err(e) = r {
+        panic!("Can't load capture {}: {}", path.display(), e);
+    }
+
+    if data.len() % 4 != 0 {
+        panic!("Capture {} isn't made of 32bit words", path.display());
+    }
+
+    data.chunks(4)
+        .map(|w| {
+            w[0] as u32 |
+            (w[1] as u32) << 8 |
+            (w[2] as u32) << 16 |
+            (w[3] as u32) << 24
+        })
+        .collect()
+}
@@ -0,0 +1,134 @@
+//! Run the CPU headless for a fixed number of instructions. This
+//! doesn't replay a captured instruction trace, the CPU runs one of
+//! two synthetic workloads:
+//!
+//! * If `RUSTATION_BENCH_BIOS` points to a BIOS image the emulator
+//!   boots it from reset, without a disc ("cpu bios boot").
+//! * Otherwise a small loop (loads, stores, ALU and branches) is run
+//!   from RAM with a dummy BIOS ("cpu synthetic loop").
+//!
+//! The number of instructions defaults to `DEFAULT_INSTRUCTIONS` and
+//! can be changed with `RUSTATION_BENCH_CPU_INSTRUCTIONS`.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustation;
+
+use std::env;
+use std::path::Path;
+
+use criterion::Criterion;
+
+use rustation::bios::Bios;
+use rustation::bios::set::BiosSet;
+use rustation::cpu::Cpu;
+use rustation::gpu::{Gpu, VideoClock};
+use rustation::gpu::renderer::NullRenderer;
+use rustation::memory::{Interconnect, Word};
+use rustation::shared::SharedState;
+
+mod common;
+
+fn cpu_run(c: &mut Criterion) {
+    let instructions = instruction_count();
+
+    let (name, bios_path) =
+        match common::env_path("RUSTATION_BENCH_BIOS") {
+            Some(bios) => ("cpu bios boot", Some(bios)),
+            None => ("cpu synthetic loop", None),
+        };
+
+    c.bench_function(name, move |b| {
+        let mut renderer = NullRenderer;
+
+        let setup = || {
+            let cpu =
+                match bios_path {
+                    Some(ref path) => boot_cpu(path),
+                    None => synthetic_cpu(),
+                };
+
+            (cpu, SharedState::new())
+        };
+
+        b.iter_with_setup(setup, |(mut cpu, mut shared)| {
+            for _ in 0..instructions {
+                cpu.run_next_instruction(&mut (), &mut shared, &mut renderer);
+            }
+
+            cpu
+        })
+    });
+}
+
+/// Build a CPU ready to run `bios` from reset
+fn boot_cpu(path: &Path) -> Cpu {
+    let mut set = BiosSet::new();
+
+    let bios =
+        match set.load_file(path) {
+            Ok(region) => set.select(region).unwrap(),
+            Err(e) => panic!("Can't load BIOS {}: {:?}", path.display(), e),
+        };
+
+    new_cpu(bios)
+}
+
+/// Build a CPU running a small loop from RAM, using a dummy BIOS
+fn synthetic_cpu() -> Cpu {
+    let mut cpu = new_cpu(Bios::dummy());
+
+    {
+        let ram = cpu.interconnect_mut().ram_mut();
+
+        for (i, &w) in SYNTHETIC_LOOP.iter().enumerate() {
+            ram.store::<Word>(0x100000 + (i * 4) as u32, w);
+        }
+    }
+
+    cpu.set_pc(0x80100000);
+
+    cpu
+}
+
+fn new_cpu(bios: Bios) -> Cpu {
+    let gpu = Gpu::new(VideoClock::Ntsc);
+
+    Cpu::new(Interconnect::new(bios, gpu, None))
+}
+
+/// Number of instructions to run, from
+/// `RUSTATION_BENCH_CPU_INSTRUCTIONS` if it's set
+fn instruction_count() -> u64 {
+    match env::var("RUSTATION_BENCH_CPU_INSTRUCTIONS") {
+        Ok(n) => match n.parse() {
+            Ok(n) => n,
+            Err(e) => panic!("Invalid instruction count {}: {}", n, e),
+        },
+        Err(_) => DEFAULT_INSTRUCTIONS,
+    }
+}
+
+/// Default number of instructions to run
+const DEFAULT_INSTRUCTIONS: u64 = 1_000_000;
+
+/// Loop at 0x80100000 summing and scrambling a word in RAM 256
+/// times before starting over
+const SYNTHETIC_LOOP: [u32; 13] = [
+    0x3c018010, // lui   $1, 0x8010
+    0x34211000, // ori   $1, $1, 0x1000
+    0x24020100, // li    $2, 0x100
+    0x8c230000, // lw    $3, 0($1)
+    0x00832021, // addu  $4, $4, $3
+    0x000428c0, // sll   $5, $4, 3
+    0x00852026, // xor   $4, $4, $5
+    0xac240000, // sw    $4, 0($1)
+    0x2442ffff, // addiu $2, $2, -1
+    0x1440fff9, // bnez  $2, 0x8010000c
+    0x00000000, // nop
+    0x08040000, // j     0x80100000
+    0x00000000, // nop
+];
+
+criterion_group!(benches, cpu_run);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! Replay a GP0 command stream through the GPU.
+//!
+//! The stream is read from the file pointed to by
+//! `RUSTATION_BENCH_GP0` (raw little endian 32bit words, in the order
+//! they were sent to the GP0 port by the CPU or the DMA). If it's not
+//! set a synthetic stream exercising the most common draw commands is
+//! used instead.
+//!
+//! Rendering itself is not measured, only the command decoding and
+//! primitive setup.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustation;
+
+use criterion::Criterion;
+
+use rustation::gpu::{Gpu, VideoClock};
+use rustation::gpu::renderer::NullRenderer;
+use rustation::shared::SharedState;
+
+mod common;
+
+fn gp0_stream(c: &mut Criterion) {
+    let (name, words) =
+        match common::env_path("RUSTATION_BENCH_GP0") {
+            Some(path) => ("gp0 capture", common::load_words(&path)),
+            None => ("gp0 synthetic", synthetic_stream()),
+        };
+
+    c.bench_function(name, move |b| {
+        let mut shared = SharedState::new();
+        let mut renderer = NullRenderer;
+
+        b.iter_with_setup(|| Gpu::new(VideoClock::Ntsc), |mut gpu| {
+            for &w in &words {
+                gpu.gp0(&mut shared, &mut renderer, w);
+            }
+
+            gpu
+        })
+    });
+}
+
+/// Build a command stream looking vaguely like a game frame: a
+/// texture upload followed by a bunch of primitives of various kinds
+fn synthetic_stream() -> Vec<u32> {
+    let mut s = Vec::new();
+
+    // Draw mode, drawing area (640x480) and drawing offset
+    s.extend_from_slice(&[0xe1000600,
+                          0xe3000000,
+                          0xe4077e7f,
+                          0xe5000000]);
+
+    // Load a 16x16 texture at 640x0
+    s.extend_from_slice(&[0xa0000000, 0x00000280, 0x00100010]);
+    s.extend((0..128).map(|i| (i * 0x00010001) ^ 0x7fff0000));
+
+    // Clear the screen
+    s.extend_from_slice(&[0x02102030, 0x00000000, 0x01e00280]);
+
+    for i in 0..256 {
+        let x = (i * 7) % 600;
+        let y = (i * 13) % 440;
+
+        let pos = |dx: u32, dy: u32| ((y + dy) << 16) | (x + dx);
+
+        // Monochrome triangle
+        s.extend_from_slice(&[0x20ff0000,
+                              pos(0, 0),
+                              pos(32, 0),
+                              pos(0, 32)]);
+
+        // Gouraud shaded quad
+        s.extend_from_slice(&[0x38ff0000, pos(0, 0),
+                              0x0000ff00, pos(32, 0),
+                              0x000000ff, pos(0, 32),
+                              0x00ffffff, pos(32, 32)]);
+
+        // Textured quad using the texture uploaded above (15bpp
+        // page at 640x0)
+        s.extend_from_slice(&[0x2c808080, pos(0, 0), 0x00000000,
+                              pos(16, 0), 0x010a0010, pos(0, 16),
+                              0x00001000, pos(16, 16), 0x00001010]);
+
+        // Monochrome line
+        s.extend_from_slice(&[0x40ffffff, pos(0, 0), pos(40, 20)]);
+    }
+
+    s
+}
+
+criterion_group!(benches, gp0_stream);
+criterion_main!(benches);
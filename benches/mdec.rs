@@ -0,0 +1,50 @@
+//! Feed a synthetic stream of quantization and IDCT table uploads to
+//! the MDEC command register.
+//!
+//! Macroblock decoding isn't implemented yet so there's nothing else
+//! worth measuring for now. A decode benchmark (and support for
+//! captured streams) should be added along with the decoder.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustation;
+
+use criterion::Criterion;
+
+use rustation::MDec;
+use rustation::shared::SharedState;
+
+fn mdec_table_upload(c: &mut Criterion) {
+    let words = synthetic_stream();
+
+    c.bench_function("mdec table upload", move |b| {
+        let mut shared = SharedState::new();
+
+        b.iter_with_setup(MDec::new, |mut mdec| {
+            for &w in &words {
+                mdec.command(&mut shared, w);
+            }
+
+            mdec
+        })
+    });
+}
+
+fn synthetic_stream() -> Vec<u32> {
+    let mut s = Vec::new();
+
+    for i in 0..64u32 {
+        // Luma and chroma quantization matrices
+        s.push(0x40000001);
+        s.extend((0..32).map(|j| (i + j).wrapping_mul(0x01010101)));
+
+        // IDCT matrix
+        s.push(0x60000000);
+        s.extend((0..32).map(|j| (i ^ j).wrapping_mul(0x5a825a82)));
+    }
+
+    s
+}
+
+criterion_group!(benches, mdec_table_upload);
+criterion_main!(benches);
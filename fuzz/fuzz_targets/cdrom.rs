@@ -0,0 +1,49 @@
+//! Feed arbitrary register accesses into the CDROM controller.
+//!
+//! The input is decoded as a sequence of 2 byte operations:
+//!
+//! * `00----rr vvvvvvvv`: store `v` to register `r`
+//! * `01----rr --------`: load from register `r`
+//! * `1ccccccc cccccccc`: let `c * 16` CPU cycles elapse before
+//!   syncing the controller, in order to let the sub-CPU process the
+//!   commands and generate the responses
+//!
+//! No disc is inserted. The crate is built with the `soft_errors`
+//! feature so unhandled commands and register accesses are only
+//! logged, any panic is reported as a crash.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate rustation;
+
+use rustation::cdrom::CdRom;
+use rustation::shared::SharedState;
+use rustation::memory::Byte;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cdrom = CdRom::new(None);
+    let mut shared = SharedState::new();
+
+    for op in data.chunks(2) {
+        if op.len() != 2 {
+            break;
+        }
+
+        let (op, arg) = (op[0], op[1]);
+
+        let offset = (op & 3) as u32;
+
+        match op >> 6 {
+            0 => cdrom.store::<Byte>(&mut shared, offset, arg as u32),
+            1 => { cdrom.load::<Byte>(&mut shared, offset); }
+            _ => {
+                let cycles = (((op as u64 & 0x7f) << 8) | arg as u64) * 16;
+
+                shared.tk().tick(cycles);
+                cdrom.sync(&mut shared);
+            }
+        }
+    }
+});
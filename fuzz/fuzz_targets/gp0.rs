@@ -0,0 +1,31 @@
+//! Feed arbitrary word streams into the GP0 command decoder.
+//!
+//! The input is split into little endian 32bit words which are sent
+//! one by one to the GP0 port, exactly like the CPU or the DMA would.
+//!
+//! The crate is built with the `soft_errors` feature so unhandled
+//! commands are only logged, any panic is reported as a crash.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate rustation;
+
+use rustation::gpu::{Gpu, VideoClock};
+use rustation::gpu::renderer::NullRenderer;
+use rustation::shared::SharedState;
+
+fuzz_target!(|data: &[u8]| {
+    let mut gpu = Gpu::new(VideoClock::Ntsc);
+    let mut shared = SharedState::new();
+    let mut renderer = NullRenderer;
+
+    for w in data.chunks(4) {
+        let word = w.iter()
+            .enumerate()
+            .fold(0u32, |word, (i, &b)| word | ((b as u32) << (i * 8)));
+
+        gpu.gp0(&mut shared, &mut renderer, word);
+    }
+});
@@ -0,0 +1,79 @@
+//! Compare two event logs (see the `rustation::event_log` module for
+//! the format) and display the first divergence.
+//!
+//! Usage: event_log_diff [--ignore-cycles] [--ignore-instructions]
+//!                       <reference.log> <other.log>
+//!
+//! Exits with status 0 if the logs match, 1 if they diverge and 2 on
+//! error.
+
+extern crate rustation;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::process;
+
+use rustation::event_log::{self, CompareOptions};
+
+fn main() {
+    let mut options = CompareOptions {
+        ignore_cycles: false,
+        ignore_instructions: false,
+    };
+
+    let mut paths = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match &*arg {
+            "--ignore-cycles" => options.ignore_cycles = true,
+            "--ignore-instructions" => options.ignore_instructions = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() != 2 {
+        fail("Usage: event_log_diff [--ignore-cycles] \
+              [--ignore-instructions] <reference.log> <other.log>");
+    }
+
+    let open = |path: &str| {
+        match File::open(path) {
+            Ok(f) => BufReader::new(f),
+            Err(e) => fail(&format!("Can't open {}: {}", path, e)),
+        }
+    };
+
+    let a = open(&paths[0]);
+    let b = open(&paths[1]);
+
+    let divergence =
+        match event_log::compare(a, b, options) {
+            Ok(d) => d,
+            Err(e) => fail(&format!("Comparison failed: {}", e)),
+        };
+
+    match divergence {
+        None => println!("Logs match"),
+        Some(d) => {
+            println!("Logs diverge:");
+
+            for &(path, line, entry) in &[(&paths[0], d.line_a, d.a),
+                                          (&paths[1], d.line_b, d.b)] {
+                match entry {
+                    Some(e) => println!("  {}:{}: {}", path, line, e),
+                    None => println!("  {}: end of log", path),
+                }
+            }
+
+            process::exit(1);
+        }
+    }
+}
+
+/// Print `msg` to stderr and exit with status 2
+fn fail(msg: &str) -> ! {
+    let _ = writeln!(io::stderr(), "{}", msg);
+
+    process::exit(2);
+}
@@ -48,7 +48,11 @@ impl fmt::Debug for Metadata {
 /// Attempt to find the metadata for the given BIOS binary blob.
 /// Returns None if this BIOS is not part of the database.
 pub fn lookup_blob(binary: &[u8; BIOS_SIZE]) -> Option<&'static Metadata> {
+    lookup_sha256(&sha256(binary))
+}
 
+/// Compute the SHA-256 of the given BIOS binary blob
+pub fn sha256(binary: &[u8; BIOS_SIZE]) -> [u8; 32] {
     let mut hasher = Sha256::new();
 
     hasher.input(binary);
@@ -57,7 +61,7 @@ pub fn lookup_blob(binary: &[u8; BIOS_SIZE]) -> Option<&'static Metadata> {
 
     hasher.result(&mut sha256);
 
-    lookup_sha256(&sha256)
+    sha256
 }
 
 /// Attempt to find the metadata for the given BIOS SHA-256
@@ -227,7 +231,9 @@ pub static DATABASE: [Metadata; 24] = [
         version_minor: 0,
         region: Region::Japan,
         known_bad: false,
-        animation_jump_hook: None,
+        // Same hook location as NA/3.0, that's what mednafen uses
+        // for fast boot with the SCPH-5500
+        animation_jump_hook: Some(0x6990),
         patch_debug_uart: None,
     },
     Metadata {
@@ -251,7 +257,9 @@ pub static DATABASE: [Metadata; 24] = [
         version_minor: 0,
         region: Region::Europe,
         known_bad: false,
-        animation_jump_hook: None,
+        // Same hook location as NA/3.0, that's what mednafen uses
+        // for fast boot with the SCPH-5502
+        animation_jump_hook: Some(0x6990),
         patch_debug_uart: None,
     },
     Metadata {
@@ -1,11 +1,13 @@
 use rustc_serialize::{Decodable, Encodable, Decoder, Encoder};
 
-use memory::Addressable;
+use memory::{Addressable, Word};
 use cdrom::disc::Region;
 
 use self::db::Metadata;
 
 pub mod db;
+pub mod set;
+pub mod patch;
 
 /// BIOS image
 pub struct Bios {
@@ -20,14 +22,33 @@ impl Bios {
 
     /// Create a BIOS image from `binary` and attempt to match it with
     /// an entry in the database. If no match can be found return
-    /// `None`.
+    /// `None`. Known bad dumps are accepted but a warning is logged
+    /// since they're likely to cause weird issues down the line.
     pub fn new(binary: Box<[u8; BIOS_SIZE]>) -> Option<Bios> {
-        match db::lookup_blob(&*binary) {
-            Some(metadata) => Some(Bios {
-                data: binary,
-                metadata: metadata,
-            }),
-            None => None,
+        let sha256 = db::sha256(&*binary);
+
+        match db::lookup_sha256(&sha256) {
+            Some(metadata) => {
+                if metadata.known_bad {
+                    warn!("BIOS {:?} is a known bad dump, expect issues",
+                          metadata);
+                } else {
+                    info!("Identified BIOS {:?}", metadata);
+                }
+
+                Some(Bios {
+                    data: binary,
+                    metadata: metadata,
+                })
+            }
+            None => {
+                let hex: Vec<String> =
+                    sha256.iter().map(|b| format!("{:02x}", b)).collect();
+
+                warn!("Unknown BIOS dump, SHA-256: {}", hex.concat());
+
+                None
+            }
         }
     }
 
@@ -51,14 +72,21 @@ impl Bios {
 
     /// Attempt to modify the BIOS ROM to remove the call to the code
     /// responsible for the boot logo animations (SCEx/PS) and
-    /// directly boot the game. This can break some games!  Returns
-    /// `Ok(())` if the code was patched, `Err(())` if we don't know
-    /// how to hack this particular BIOS.
+    /// directly boot the game ("fast boot"). This can break some
+    /// games!  Returns `Ok(())` if the code was patched, `Err(())` if
+    /// we don't know how to hack this particular BIOS.
     pub fn patch_boot_animation(&mut self) -> Result<(), ()> {
         // Set the logo jump to `0` (NOP)
         self.patch_animation_jump_hook(0)
     }
 
+    /// Returns `true` if we know where the boot animation call is
+    /// located in this BIOS, in which case `patch_boot_animation`
+    /// and `patch_animation_jump_hook` should succeed.
+    pub fn supports_fast_boot(&self) -> bool {
+        self.metadata.animation_jump_hook.is_some()
+    }
+
     /// Attempt to modify the BIOS ROM to replace the call to the code
     /// responsible for the boot logo animations by the provided
     /// instruction.
@@ -66,6 +94,21 @@ impl Bios {
                                      instruction: u32) -> Result<(), ()> {
         match self.metadata.animation_jump_hook {
             Some(h) => {
+                let cur = self.load::<Word>(h);
+
+                // Make sure we're about to overwrite the original
+                // `jal` (or a previous patch) and not some random
+                // piece of code, otherwise the BIOS will almost
+                // certainly crash in some weird way later on.
+                let is_jal = cur >> 26 == 3;
+
+                if !is_jal && cur != 0 {
+                    warn!("Unexpected instruction at BIOS animation \
+                           hook 0x{:x}: 0x{:08x}, not patching",
+                          h, cur);
+                    return Err(());
+                }
+
                 let h = h as usize;
 
                 self.data[h]     = instruction as u8;
@@ -91,6 +134,21 @@ impl Bios {
         }
     }
 
+    /// Apply a single patch to the BIOS image
+    pub fn apply_patch(&mut self,
+                       patch: &patch::Patch) -> Result<(), patch::Error> {
+        patch.apply(&mut self.data)
+    }
+
+    /// Apply a list of patches to the BIOS image. All the patches are
+    /// checked against the unmodified image before anything is
+    /// written so if an error is returned the BIOS is left
+    /// untouched. Patches are then written in order.
+    pub fn apply_patches(&mut self,
+                         patches: &[patch::Patch]) -> Result<(), patch::Error> {
+        patch::apply_list(patches, &mut self.data)
+    }
+
     /// fetch the little endian value at `offset`
     pub fn load<T: Addressable>(&self, offset: u32) -> u32 {
         let offset = offset as usize;
@@ -108,6 +166,38 @@ impl Bios {
     pub fn metadata(&self) -> &'static Metadata {
         self.metadata
     }
+
+    /// Return the region of this BIOS
+    pub fn region(&self) -> Region {
+        self.metadata.region
+    }
+
+    /// Return the version of this BIOS as a `(major, minor)` pair
+    pub fn version(&self) -> (u8, u8) {
+        (self.metadata.version_major, self.metadata.version_minor)
+    }
+
+    /// Returns `true` if this BIOS can boot discs from `region`
+    /// without a modchip. If it can't the BIOS will usually just
+    /// stop at the logo screen or hang.
+    pub fn matches_region(&self, region: Region) -> bool {
+        self.metadata.region == region
+    }
+}
+
+impl Clone for Bios {
+    fn clone(&self) -> Bios {
+        // Can't derive this, `Clone` isn't implemented for large
+        // arrays
+        let mut data = box_array![0; BIOS_SIZE];
+
+        data.copy_from_slice(&*self.data);
+
+        Bios {
+            data: data,
+            metadata: self.metadata,
+        }
+    }
 }
 
 impl Encodable for Bios {
@@ -0,0 +1,267 @@
+//! Generic BIOS patching, used to apply arbitrary modifications to
+//! the BIOS image after it's been loaded (enabling the TTY output,
+//! unlocking debug menus, bypassing the region check...) without
+//! having to hex-edit the dump.
+//!
+//! Patch lists can be parsed from a simple text format, one patch
+//! per line:
+//!
+//! ```text
+//! # <offset> <new bytes> [<expected bytes>]
+//! # Replace the word at 0x6990 with a NOP
+//! 0x6990 00000000
+//! ```
+//!
+//! `offset` is the offset in the BIOS image (in hexadecimal, the
+//! `0x` prefix is optional). The bytes are given in hexadecimal in
+//! the order they appear in the image (so little endian for
+//! words). If the optional `expected` bytes are given the patch is
+//! only applied if the BIOS contains those bytes at `offset`, which
+//! can be used to make sure we're not patching the wrong BIOS.
+
+use std::fmt;
+
+use super::BIOS_SIZE;
+
+/// A single BIOS patch
+#[derive(Clone, Debug)]
+pub struct Patch {
+    /// Offset of the patch in the BIOS image
+    pub offset: u32,
+    /// Bytes to be written at `offset`
+    pub data: Vec<u8>,
+    /// If not `None` the patch is only applied if the current
+    /// contents of the BIOS at `offset` match these bytes. Must be
+    /// the same length as `data`.
+    pub expected: Option<Vec<u8>>,
+}
+
+impl Patch {
+    /// Check that the patch can be applied to `image`
+    pub fn check(&self, image: &[u8; BIOS_SIZE]) -> Result<(), Error> {
+        let start = self.offset as usize;
+        let end = start + self.data.len();
+
+        if end > BIOS_SIZE {
+            return Err(Error::OutOfBounds(self.offset));
+        }
+
+        if let Some(ref expected) = self.expected {
+            if expected.len() != self.data.len() ||
+                &image[start..end] != &expected[..] {
+                return Err(Error::Mismatch(self.offset));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the patch to `image` if `check` succeeds
+    pub fn apply(&self, image: &mut [u8; BIOS_SIZE]) -> Result<(), Error> {
+        try!(self.check(image));
+
+        self.write(image);
+
+        Ok(())
+    }
+
+    /// Write the patch to `image` without any check
+    fn write(&self, image: &mut [u8; BIOS_SIZE]) {
+        let start = self.offset as usize;
+        let end = start + self.data.len();
+
+        image[start..end].copy_from_slice(&self.data);
+    }
+}
+
+/// Apply a list of patches to `image`. All the patches are checked
+/// against the unmodified image before anything is written so if an
+/// error is returned `image` is left untouched. Patches are then
+/// written in order.
+pub fn apply_list(patches: &[Patch],
+                  image: &mut [u8; BIOS_SIZE]) -> Result<(), Error> {
+    for p in patches {
+        try!(p.check(image));
+    }
+
+    for p in patches {
+        p.write(image);
+    }
+
+    Ok(())
+}
+
+/// Parse a list of patches using the format described in the module
+/// documentation.
+pub fn parse_patch_list(list: &str) -> Result<Vec<Patch>, ParseError> {
+    let mut patches = Vec::new();
+
+    for (i, line) in list.lines().enumerate() {
+        // Strip comments
+        let line =
+            match line.find('#') {
+                Some(p) => &line[..p],
+                None => line,
+            };
+
+        let mut fields = line.split_whitespace();
+
+        let offset =
+            match fields.next() {
+                Some(o) => o,
+                // Empty line
+                None => continue,
+            };
+
+        // Line numbers start at 1
+        let line = i + 1;
+
+        let offset =
+            if offset.starts_with("0x") {
+                &offset[2..]
+            } else {
+                offset
+            };
+
+        let offset =
+            match u32::from_str_radix(offset, 16) {
+                Ok(o) => o,
+                Err(_) => return Err(ParseError::new(line, "bad offset")),
+            };
+
+        let data =
+            match fields.next().and_then(parse_hex_bytes) {
+                Some(d) => d,
+                None => return Err(ParseError::new(line, "bad patch data")),
+            };
+
+        let expected =
+            match fields.next() {
+                Some(e) => {
+                    match parse_hex_bytes(e) {
+                        Some(e) => {
+                            if e.len() != data.len() {
+                                return Err(ParseError::new(
+                                    line,
+                                    "expected data length mismatch"));
+                            }
+
+                            Some(e)
+                        }
+                        None => return Err(ParseError::new(
+                            line,
+                            "bad expected data")),
+                    }
+                }
+                None => None,
+            };
+
+        if fields.next().is_some() {
+            return Err(ParseError::new(line, "too many fields"));
+        }
+
+        patches.push(Patch {
+            offset: offset,
+            data: data,
+            expected: expected,
+        });
+    }
+
+    Ok(patches)
+}
+
+/// Parse a string of hexadecimal digit pairs into bytes
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let digits: Option<Vec<u32>> =
+        s.chars().map(|c| c.to_digit(16)).collect();
+
+    let digits =
+        match digits {
+            Some(d) => d,
+            None => return None,
+        };
+
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+
+    Some(digits.chunks(2).map(|p| ((p[0] << 4) | p[1]) as u8).collect())
+}
+
+/// Error returned when a patch can't be applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The patch at this offset goes past the end of the BIOS image
+    OutOfBounds(u32),
+    /// The BIOS contents at this offset don't match the expected
+    /// bytes
+    Mismatch(u32),
+}
+
+/// Error returned when a patch list can't be parsed
+#[derive(Debug)]
+pub struct ParseError {
+    /// Line where the error occurred (starting at 1)
+    pub line: usize,
+    /// Description of the error
+    pub reason: &'static str,
+}
+
+impl ParseError {
+    fn new(line: usize, reason: &'static str) -> ParseError {
+        ParseError {
+            line: line,
+            reason: reason,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[test]
+fn parse() {
+    let list = "\
+# Comment line
+
+0x6990 00000000 # Trailing comment
+   1234   abCD   0102
+";
+
+    let patches = parse_patch_list(list).unwrap();
+
+    assert!(patches.len() == 2);
+
+    assert!(patches[0].offset == 0x6990);
+    assert!(patches[0].data == [0, 0, 0, 0]);
+    assert!(patches[0].expected.is_none());
+
+    assert!(patches[1].offset == 0x1234);
+    assert!(patches[1].data == [0xab, 0xcd]);
+    assert!(patches[1].expected == Some(vec![0x01, 0x02]));
+
+    assert!(parse_patch_list("").unwrap().is_empty());
+    assert!(parse_patch_list("# Nothing here\n\n").unwrap().is_empty());
+}
+
+#[test]
+fn parse_errors() {
+    fn error(list: &str) -> (usize, &'static str) {
+        let e = parse_patch_list(list).unwrap_err();
+
+        (e.line, e.reason)
+    }
+
+    assert!(error("0x10") == (1, "bad patch data"));
+    assert!(error("\n0x10 zz") == (2, "bad patch data"));
+    assert!(error("0x10 123") == (1, "bad patch data"));
+    assert!(error("0x10 0x12") == (1, "bad patch data"));
+    assert!(error("0xg0 12") == (1, "bad offset"));
+    assert!(error("0x0x10 12") == (1, "bad offset"));
+    assert!(error("0x10 12 3") == (1, "bad expected data"));
+    assert!(error("0x10 12 3456") == (1, "expected data length mismatch"));
+    assert!(error("0x10 12 34 56") == (1, "too many fields"));
+}
@@ -0,0 +1,270 @@
+//! Management of multiple BIOS images in order to select the right
+//! one depending on the region of the disc being loaded.
+
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, Read};
+
+use cdrom::disc::{Disc, Region};
+
+use super::{Bios, BIOS_SIZE};
+
+/// A set of BIOS images, at most one per region
+pub struct BiosSet {
+    /// BIOS images indexed by `region_index`
+    bios: [Option<Bios>; 3],
+}
+
+impl BiosSet {
+    /// Create an empty set
+    pub fn new() -> BiosSet {
+        BiosSet {
+            bios: [None, None, None],
+        }
+    }
+
+    /// Add `bios` to the set. If a BIOS was already present for the
+    /// same region it's replaced and returned.
+    pub fn insert(&mut self, bios: Bios) -> Option<Bios> {
+        let region = bios.region();
+        let slot = &mut self.bios[region_index(region)];
+
+        if let Some(ref old) = *slot {
+            warn!("Replacing BIOS {:?} with {:?}",
+                  old.metadata(), bios.metadata());
+        }
+
+        ::std::mem::replace(slot, Some(bios))
+    }
+
+    /// Load a BIOS image from `path` and add it to the set. Returns
+    /// the region of the loaded BIOS.
+    pub fn load_file(&mut self, path: &Path) -> Result<Region, Error> {
+        let file = try!(File::open(path));
+
+        self.load(file)
+    }
+
+    /// Load a BIOS image from `reader` and add it to the set. Returns
+    /// the region of the loaded BIOS.
+    pub fn load<R: Read>(&mut self, reader: R) -> Result<Region, Error> {
+        let mut buf = Vec::with_capacity(BIOS_SIZE);
+
+        // Read one more byte than expected to catch oversized images
+        try!(reader.take(BIOS_SIZE as u64 + 1).read_to_end(&mut buf));
+
+        if buf.len() != BIOS_SIZE {
+            return Err(Error::BadSize);
+        }
+
+        let mut data = box_array![0; BIOS_SIZE];
+
+        data.copy_from_slice(&buf);
+
+        match Bios::new(data) {
+            Some(bios) => {
+                let region = bios.region();
+
+                self.insert(bios);
+
+                Ok(region)
+            }
+            None => Err(Error::UnknownDump),
+        }
+    }
+
+    /// Return true if we have a BIOS for `region`
+    pub fn has_region(&self, region: Region) -> bool {
+        self.bios[region_index(region)].is_some()
+    }
+
+    /// Return a copy of the BIOS best suited to boot a disc from
+    /// `region`. If we don't have the correct BIOS we fall back on a
+    /// BIOS from a different region (preferably one using the same
+    /// video standard) and log a warning since the game will most
+    /// likely refuse to boot. Returns `None` if the set is empty.
+    ///
+    /// The set is left untouched so it can be reused when the disc
+    /// is changed.
+    pub fn select(&self, region: Region) -> Option<Bios> {
+        if let Some(ref bios) = self.bios[region_index(region)] {
+            return Some(bios.clone());
+        }
+
+        let fallbacks =
+            match region {
+                Region::Japan =>
+                    [Region::NorthAmerica, Region::Europe],
+                Region::NorthAmerica =>
+                    [Region::Japan, Region::Europe],
+                Region::Europe =>
+                    [Region::NorthAmerica, Region::Japan],
+            };
+
+        for &r in &fallbacks {
+            if let Some(ref bios) = self.bios[region_index(r)] {
+                warn!("No {:?} BIOS available, falling back on {:?}",
+                      region, bios.metadata());
+
+                return Some(bios.clone());
+            }
+        }
+
+        warn!("No BIOS available for region {:?}", region);
+
+        None
+    }
+
+    /// Return a copy of the BIOS best suited to boot `disc`, see
+    /// `select` for the fallback rules.
+    pub fn select_for(&self, disc: &Disc) -> Option<Bios> {
+        self.select(disc.region())
+    }
+}
+
+fn region_index(region: Region) -> usize {
+    match region {
+        Region::Japan => 0,
+        Region::NorthAmerica => 1,
+        Region::Europe => 2,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error while reading the BIOS file
+    IoError(io::Error),
+    /// The BIOS image doesn't have the expected size
+    BadSize,
+    /// The BIOS image is not in our database
+    UnknownDump,
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::IoError(err)
+    }
+}
+
+#[cfg(test)]
+fn fixture(metadata: &'static super::db::Metadata) -> Bios {
+    Bios {
+        data: box_array![0; BIOS_SIZE],
+        metadata: metadata,
+    }
+}
+
+#[cfg(test)]
+fn fixture_metadata(region: Region) -> &'static super::db::Metadata {
+    use super::db::Metadata;
+
+    static JAPAN: Metadata = Metadata {
+        sha256: [0; 32],
+        version_major: 1,
+        version_minor: 0,
+        region: Region::Japan,
+        known_bad: false,
+        animation_jump_hook: None,
+        patch_debug_uart: None,
+    };
+
+    static NORTH_AMERICA: Metadata = Metadata {
+        sha256: [1; 32],
+        version_major: 1,
+        version_minor: 0,
+        region: Region::NorthAmerica,
+        known_bad: false,
+        animation_jump_hook: None,
+        patch_debug_uart: None,
+    };
+
+    static EUROPE: Metadata = Metadata {
+        sha256: [2; 32],
+        version_major: 1,
+        version_minor: 0,
+        region: Region::Europe,
+        known_bad: false,
+        animation_jump_hook: None,
+        patch_debug_uart: None,
+    };
+
+    match region {
+        Region::Japan => &JAPAN,
+        Region::NorthAmerica => &NORTH_AMERICA,
+        Region::Europe => &EUROPE,
+    }
+}
+
+#[test]
+fn select() {
+    let selected = |set: &BiosSet, region| {
+        set.select(region).map(|b| b.region())
+    };
+
+    let mut set = BiosSet::new();
+
+    assert_eq!(selected(&set, Region::Europe), None);
+
+    set.insert(fixture(fixture_metadata(Region::Japan)));
+
+    // Only one BIOS, used for everything
+    assert_eq!(selected(&set, Region::Japan), Some(Region::Japan));
+    assert_eq!(selected(&set, Region::NorthAmerica), Some(Region::Japan));
+    assert_eq!(selected(&set, Region::Europe), Some(Region::Japan));
+
+    set.insert(fixture(fixture_metadata(Region::NorthAmerica)));
+
+    // PAL discs prefer the NTSC-U BIOS over the NTSC-J one
+    assert_eq!(selected(&set, Region::Europe), Some(Region::NorthAmerica));
+    assert_eq!(selected(&set, Region::Japan), Some(Region::Japan));
+
+    set.insert(fixture(fixture_metadata(Region::Europe)));
+
+    assert_eq!(selected(&set, Region::Europe), Some(Region::Europe));
+    assert_eq!(selected(&set, Region::NorthAmerica),
+               Some(Region::NorthAmerica));
+
+    // Selecting doesn't consume the images
+    assert!(set.has_region(Region::Japan));
+    assert!(set.has_region(Region::NorthAmerica));
+    assert!(set.has_region(Region::Europe));
+}
+
+#[test]
+fn insert_replaces() {
+    let mut set = BiosSet::new();
+
+    assert!(set.insert(fixture(fixture_metadata(Region::Europe))).is_none());
+
+    let old = set.insert(fixture(fixture_metadata(Region::Europe)));
+
+    assert_eq!(old.map(|b| b.region()), Some(Region::Europe));
+}
+
+#[test]
+fn load_errors() {
+    let mut set = BiosSet::new();
+
+    let short = vec![0; BIOS_SIZE - 1];
+    let long = vec![0; BIOS_SIZE + 1];
+    let unknown = vec![0; BIOS_SIZE];
+
+    match set.load(&short[..]) {
+        Err(Error::BadSize) => (),
+        r => panic!("Unexpected result for short image: {:?}", r),
+    }
+
+    match set.load(&long[..]) {
+        Err(Error::BadSize) => (),
+        r => panic!("Unexpected result for long image: {:?}", r),
+    }
+
+    match set.load(&unknown[..]) {
+        Err(Error::UnknownDump) => (),
+        r => panic!("Unexpected result for unknown image: {:?}", r),
+    }
+
+    assert!(!set.has_region(Region::Japan));
+    assert!(!set.has_region(Region::NorthAmerica));
+    assert!(!set.has_region(Region::Europe));
+}
@@ -19,6 +19,7 @@ use memory::Addressable;
 use timekeeper::{Peripheral, Cycles};
 use interrupt::Interrupt;
 use shared::SharedState;
+use tracer::module_tracer;
 use arrayvec::ArrayVec;
 use cdimage::sector::Sector;
 use cdimage::msf::Msf;
@@ -259,15 +260,16 @@ impl CdRom {
         self.sync(shared);
 
         if T::size() != 1 {
-            panic!("Unhandled CDROM load ({})", T::size());
+            unhandled!((), "Unhandled CDROM load ({})", T::size());
         }
 
         let index = self.index;
 
-        let unimplemented = || {
-            panic!("read CDROM register {}.{}",
-                   offset,
-                   index)
+        let unimplemented = || -> u8 {
+            unhandled!(0,
+                       "read CDROM register {}.{}",
+                       offset,
+                       index)
         };
 
         // CXD1199AQ Datasheet section 3 documents the host interface
@@ -284,7 +286,9 @@ impl CdRom {
                     // response bytes.
 
                     if self.host_response.is_empty() {
-                        panic!("CDROM response FIFO underflow");
+                        // The FIFO just wraps around, it's probably a
+                        // software bug though
+                        unhandled!((), "CDROM response FIFO underflow");
                     }
 
                     self.host_response.pop()
@@ -311,7 +315,7 @@ impl CdRom {
         self.sync(shared);
 
         if T::size() != 1 {
-            panic!("Unhandled CDROM store ({})", T::size());
+            unhandled!((), "Unhandled CDROM store ({})", T::size());
         }
 
         // All writeable registers are 8bit wide
@@ -320,10 +324,11 @@ impl CdRom {
         let index = self.index;
 
         let unimplemented = || {
-            panic!("write CDROM register {}.{} {:x}",
-                   offset,
-                   index,
-                   val)
+            unhandled!((),
+                       "write CDROM register {}.{} {:x}",
+                       offset,
+                       index,
+                       val)
         };
 
         match offset {
@@ -358,7 +363,8 @@ impl CdRom {
                         }
 
                         if val & 0xa0 != 0 {
-                            panic!("Unhandled CDROM 3.1: {:02x}", val);
+                            unhandled!((),
+                                       "Unhandled CDROM 3.1: {:02x}", val);
                         }
                     }
                     // ATV1 register
@@ -413,23 +419,29 @@ impl CdRom {
     /// COMMAND register write
     fn set_command(&mut self, shared: &mut SharedState, cmd: u8) {
         if let Some(c) = self.command {
-            panic!("Nested CDC command! ({:02x} + {:02x})", c, cmd);
+            // Drop the new command
+            unhandled!(return,
+                       "Nested CDC command! ({:02x} + {:02x})", c, cmd);
         }
 
         self.command = Some(cmd);
 
+        module_tracer("CDROM", |m| {
+            m.event(shared.tk().now(), "command", cmd);
+        });
+
         self.maybe_start_command(shared);
     }
 
     /// PARAMETER register write
     fn set_parameter(&mut self, param: u8) {
         if let Some(c) = self.command {
-            panic!("Parameter push during command {:02x})", c);
+            unhandled!((), "Parameter push during command {:02x})", c);
         }
 
         if self.host_params.is_full() {
             // Wraps around on real hardware
-            panic!("CDROM parameter FIFO overflow");
+            unhandled!(return, "CDROM parameter FIFO overflow");
         }
 
         self.host_params.push(param);
@@ -480,13 +492,17 @@ impl CdRom {
         }
 
         if ctrl & 0x7f != 0 {
-            panic!("CDROM: unhandled HCHPCTL {:02x}", ctrl);
+            unhandled!((), "CDROM: unhandled HCHPCTL {:02x}", ctrl);
         }
     }
 
     fn irq_ack(&mut self, shared: &mut SharedState, v: u8) {
         self.irq_flags &= !v;
 
+        module_tracer("CDROM", |m| {
+            m.trace(shared.tk().now(), "irq_flags", self.irq_flags);
+        });
+
         // Check if a command/async/read event was waiting for the IRQ
         // ack to process.
         //
@@ -647,13 +663,20 @@ impl CdRom {
     /// Trigger an interrupt and check if it must be sent to the main
     /// interrupt controller
     fn trigger_irq(&mut self, shared: &mut SharedState, irq: IrqCode) {
+        // Sequences only start once all the interrupts have been
+        // acknowledged and there's never more than one running so
+        // the guest can't get us here with `irq_flags` set.
         assert!(self.irq_flags == 0);
 
         self.irq_flags = irq as u8;
 
+        module_tracer("CDROM", |m| {
+            m.trace(shared.tk().now(), "irq_flags", self.irq_flags);
+        });
+
         if self.irq() {
             // Interrupt rising edge
-            shared.irq_state_mut().assert(Interrupt::CdRom);
+            shared.assert_irq(Interrupt::CdRom);
         }
     }
 
@@ -675,7 +698,7 @@ impl CdRom {
                 self.rx_active = false;
             }
         } else {
-            panic!("read byte while !rx_active");
+            unhandled!((), "read byte while !rx_active");
         }
 
         b
@@ -697,7 +720,7 @@ impl CdRom {
         // Make sure we don't end up in track1's pregap, I don't know
         // if it's ever useful? Needs special handling at least...
         if self.seek_target < Msf::from_bcd(0x00, 0x02, 0x00).unwrap() {
-            panic!("Seek to track. 1 pregap: {}", self.seek_target);
+            unhandled!((), "Seek to track. 1 pregap: {}", self.seek_target);
         }
 
         self.position = self.seek_target;
@@ -707,7 +730,7 @@ impl CdRom {
     /// Called when a new sector must be read
     fn read_sector(&mut self) {
         if self.read_pending {
-            panic!("Sector read while previous one is still pending");
+            unhandled!((), "Sector read while previous one is still pending");
         }
 
         let position = self.position;
@@ -717,9 +740,9 @@ impl CdRom {
             Some(ref mut d) =>
                 if let Err(e) = d.image().read_sector(&mut self.sector,
                                                       position) {
-                    panic!("Couldn't read sector: {}", e);
+                    unhandled!(return, "Couldn't read sector: {}", e);
                 },
-            None => panic!("Sector read without a disc"),
+            None => unhandled!(return, "Sector read without a disc"),
         }
 
         {
@@ -732,8 +755,9 @@ impl CdRom {
                         match self.sector.data_2352() {
                             Ok(d) => d,
                             Err(e) =>
-                                panic!("Failed to read whole sector {}: {}",
-                                       position, e),
+                                unhandled!(return,
+                                           "Failed to read whole sector {}: {}",
+                                           position, e),
                         };
 
                     // Skip the sync pattern
@@ -744,8 +768,9 @@ impl CdRom {
                         match self.sector.mode2_xa_payload() {
                             Ok(d) => d as &[u8],
                             Err(e) =>
-                                panic!("Failed to read sector {}: {}",
-                                       position, e),
+                                unhandled!(return,
+                                           "Failed to read sector {}: {}",
+                                           position, e),
                         };
 
                     if data.len() > 2048 {
@@ -772,7 +797,7 @@ impl CdRom {
         self.position =
             match self.position.next() {
                 Some(m) => m,
-                None => panic!("MSF overflow!"),
+                None => unhandled!(self.position, "MSF overflow!"),
             };
 
         self.read_pending = true;
@@ -825,15 +850,19 @@ impl CdRom {
                 // ReadS
                 0x1b => (0, 0, CdRom::cmd_read),
                 0x1e => (0, 0, CdRom::cmd_read_toc),
-                c => panic!("Unhandled CDROM command 0x{:02x} {:?}",
-                            c, self.sub_cpu.params),
+                // XXX The real controller returns an error code
+                c => unhandled!((0, 16, CdRom::cmd_get_stat),
+                                "Unhandled CDROM command 0x{:02x} {:?}",
+                                c, self.sub_cpu.params),
             };
 
         let nparams = self.sub_cpu.params.len();
 
         if nparams < min_param || nparams > max_param {
-            panic!("Wrong number of parameters for command {:02x} ({})",
-                   self.command.unwrap(), nparams);
+            // XXX The real controller returns an error code
+            unhandled!({ self.cmd_get_stat(); return },
+                       "Wrong number of parameters for command {:02x} ({})",
+                       self.command.unwrap(), nparams);
         }
 
         handler(self);
@@ -859,8 +888,10 @@ impl CdRom {
             match Msf::from_bcd(m, s, f) {
                 Some(m) => m,
                 // XXX: what happens if invalid BCD is used?
-                None => panic!("Invalid MSF in set loc: {:02x}:{:02x}:{:02x}",
-                               m, s, f),
+                None => unhandled!(self.seek_target,
+                                   "Invalid MSF in set loc: \
+                                    {:02x}:{:02x}:{:02x}",
+                                   m, s, f),
             };
 
         self.seek_target_pending = true;
@@ -1003,7 +1034,7 @@ impl CdRom {
            self.autopause ||
            self.report_interrupts ||
            self.sector_size_override {
-            panic!("CDROM: unhandled mode: {:02x}", mode);
+            unhandled!((), "CDROM: unhandled mode: {:02x}", mode);
         }
 
         let status = self.drive_status();
@@ -1043,7 +1074,8 @@ impl CdRom {
             //
             // For instance after seeking at 00:01:25 the track MSF
             // returned by GetLocP is 00:00:49 with my PAL Spyro disc.
-            panic!("GetLocP while in track1 pregap");
+            unhandled!({ self.cmd_get_stat(); return },
+                       "GetLocP while in track1 pregap");
         }
 
         // Fixme: All this data should be extracted from the
@@ -1106,13 +1138,17 @@ impl CdRom {
     /// parameter says what
     fn cmd_test(&mut self) {
         if self.sub_cpu.params.len() != 1 {
-            panic!("Unexpected number of parameters for CDROM test command: {}",
-                   self.sub_cpu.params.len());
+            unhandled!(return,
+                       "Unexpected number of parameters for CDROM test \
+                        command: {}",
+                       self.sub_cpu.params.len());
         }
 
         match self.sub_cpu.params.pop() {
              0x20 => self.test_version(),
-             n    => panic!("Unhandled CDROM test subcommand 0x{:02x}", n),
+             n    => unhandled!(self.cmd_get_stat(),
+                                "Unhandled CDROM test subcommand 0x{:02x}",
+                                n),
         }
     }
 
@@ -1356,7 +1392,14 @@ impl SubCpu {
             // command dependant. Can't really see why anybody would
             // want to start a new command without waiting for the
             // response to the previous one though.
-            panic!("New CD command while still waiting for an async response");
+            unhandled!((),
+                       "New CD command while still waiting for an async \
+                        response");
+
+            // Drop the pending response and let the new command take
+            // over, otherwise we'd end up with two async responses
+            // if it's an async command as well.
+            self.async_response = None;
         }
 
         self.sequence = SubCpuSequence::CommandPending;
@@ -1372,6 +1415,8 @@ impl SubCpu {
     fn schedule_async_response(&mut self,
                                delay: u32,
                                handler: fn (&mut CdRom) -> u32) {
+        // Can't fail: `start_command` drops any pending async
+        // response and commands schedule at most one
         assert!(self.async_response.is_none());
 
         self.async_response = Some((delay, AsyncResponse(handler)));
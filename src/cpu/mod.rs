@@ -13,6 +13,7 @@ use gpu::renderer::Renderer;
 use interrupt::InterruptState;
 use debugger::Debugger;
 use tracer::module_tracer;
+use event_log::{self, Event};
 
 use self::cop0::{Cop0, Exception};
 use self::gte::Gte;
@@ -140,6 +141,10 @@ impl Cpu {
             return;
         }
 
+        // Date of the instruction for the event log, taken before the
+        // fetch cycles are counted
+        let date = shared.tk().now();
+
         // Fetch instruction at PC
         let instruction = self.fetch_instruction(shared);
 
@@ -175,6 +180,7 @@ impl Cpu {
                 // occurs
                 self.decode_and_execute(debugger,
                                         instruction,
+                                        date,
                                         shared,
                                         renderer);
             }
@@ -186,7 +192,11 @@ impl Cpu {
             self.exception(Exception::Interrupt);
         } else {
             // No interrupt pending, run the current instruction
-            self.decode_and_execute(debugger, instruction, shared, renderer);
+            self.decode_and_execute(debugger,
+                                    instruction,
+                                    date,
+                                    shared,
+                                    renderer);
         }
     }
 
@@ -276,7 +286,14 @@ impl Cpu {
     where A: Addressable, D: Debugger {
         debugger.memory_read(self, addr);
 
-        self.inter.load::<A>(shared, addr)
+        // Events are dated before the access takes place
+        let date = shared.tk().now();
+
+        let v = self.inter.load::<A>(shared, addr);
+
+        event_log::log_io(date, addr, || Event::Load(A::size(), addr, v));
+
+        v
     }
 
     /// Memory read with as little side-effect as possible. Used for
@@ -308,6 +325,10 @@ impl Cpu {
         if self.cop0.cache_isolated() {
             self.cache_maintenance::<A>(addr, val);
         } else {
+            event_log::log_io(shared.tk().now(),
+                              addr,
+                              || Event::Store(A::size(), addr, val));
+
             self.inter.store::<A>(shared, renderer, addr, val);
         }
     }
@@ -458,13 +479,19 @@ impl Cpu {
         self.delay_slot = false;
     }
 
-    /// Decode `instruction`'s opcode and run the function
+    /// Decode `instruction`'s opcode and run the function. `date` is
+    /// the date of the instruction fetch, used for the event log.
     fn decode_and_execute<D>(&mut self,
                              debugger: &mut D,
                              instruction: Instruction,
+                             date: u64,
                              shared: &mut SharedState,
                              renderer: &mut Renderer)
         where D: Debugger {
+        let pc = self.current_pc;
+
+        event_log::log(date, || Event::Execute(pc, instruction.0));
+
         // Simulate instruction execution time.
         shared.tk().tick(1);
 
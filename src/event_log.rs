@@ -0,0 +1,513 @@
+//! Canonical per-event log used to compare the behaviour of the
+//! emulator against other emulators (or against itself, after a
+//! change) running the same input movie.
+//!
+//! Logging is only available when the crate is built with the
+//! `event_log` feature, it's started with `start` and stopped with
+//! `stop`. Parsing and comparing logs is always available, see the
+//! `event_log_diff` tool.
+//!
+//! # Format
+//!
+//! The log is a text file with one event per line. Empty lines and
+//! lines starting with a `#` are ignored. Each event line starts with
+//! the date of the event in CPU cycles since reset (in decimal)
+//! followed by the event type and its fields, separated by
+//! whitespace. All the other numbers are in hexadecimal without
+//! prefix.
+//!
+//! The date is always taken when the event starts, before any cycle
+//! it costs is counted: an `X` event is dated before the instruction
+//! is fetched and `R`/`W` events before the bus access takes place.
+//! An `I` event is dated when the interrupt is asserted.
+//!
+//! Events are written in the order they complete, which isn't always
+//! the order of their dates: an `R` event can only be written once
+//! the load has returned its value, so `I` events raised while the
+//! peripherals are synchronized during that load appear before it in
+//! the file even though they're dated later. Both logs being
+//! compared are written the same way so this doesn't cause spurious
+//! divergences between two rustation runs.
+//!
+//! # Comparing against other emulators
+//!
+//! No other emulator emits this format: comparing against mednafen
+//! or DuckStation requires patching them to write it, and no such
+//! patch or converter is provided here. The format is meant to be
+//! easy to produce from any interpreter: log an `X` line when an
+//! instruction is dispatched (skipping the ones replaced by an
+//! interrupt), `R`/`W` lines for the CPU's accesses outside of RAM,
+//! the scratchpad and the BIOS, and an `I` line whenever a bit is set
+//! in the interrupt status register. Timings differ between
+//! emulators so such comparisons should use `--ignore-cycles`.
+//!
+//! Events:
+//!
+//!
+//! * `<cycle> X <pc> <instruction>`: the CPU starts executing
+//!   `instruction` (the raw 32bit word) at address `pc`. Instructions
+//!   preempted by an interrupt are not logged.
+//! * `<cycle> R <size> <address> <value>`: I/O load of `size` bytes
+//!   (1, 2 or 4) at `address` (as seen by the CPU, including the
+//!   region bits) returning `value`.
+//! * `<cycle> W <size> <address> <value>`: I/O store of `value`.
+//! * `<cycle> I <status>`: an interrupt has been asserted, `status`
+//!   is the new value of the interrupt status register.
+//!
+//! Only accesses outside of RAM, the scratchpad and the BIOS are
+//! considered I/O accesses. The loads and stores issued by an
+//! instruction are logged after its `X` event.
+//!
+//! Example:
+//!
+//! ```text
+//! 1234 X bfc00000 3c080013
+//! 1280 W 4 1f801010 0013243f
+//! 5678 I 0001
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// A single logged event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Instruction execution: `pc` and instruction word
+    Execute(u32, u32),
+    /// I/O load: size in bytes, address and value
+    Load(u8, u32, u32),
+    /// I/O store: size in bytes, address and value
+    Store(u8, u32, u32),
+    /// Interrupt asserted: new interrupt status
+    Irq(u16),
+}
+
+/// An event along with its date
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Date of the event in CPU cycles
+    pub cycle: u64,
+    pub event: Event,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} ", self.cycle));
+
+        match self.event {
+            Event::Execute(pc, i) =>
+                write!(f, "X {:08x} {:08x}", pc, i),
+            Event::Load(size, addr, val) =>
+                write!(f, "R {} {:08x} {:08x}", size, addr, val),
+            Event::Store(size, addr, val) =>
+                write!(f, "W {} {:08x} {:08x}", size, addr, val),
+            Event::Irq(status) =>
+                write!(f, "I {:04x}", status),
+        }
+    }
+}
+
+impl FromStr for Entry {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Entry, ()> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+
+        if fields.len() < 2 {
+            return Err(());
+        }
+
+        let cycle = try!(u64::from_str(fields[0]).map_err(|_| ()));
+
+        let hex = |i: usize| -> Result<u32, ()> {
+            match fields.get(i) {
+                Some(f) => u32::from_str_radix(f, 16).map_err(|_| ()),
+                None => Err(()),
+            }
+        };
+
+        let size = || -> Result<u8, ()> {
+            match fields.get(2).map(|s| *s) {
+                Some("1") => Ok(1),
+                Some("2") => Ok(2),
+                Some("4") => Ok(4),
+                _ => Err(()),
+            }
+        };
+
+        let (event, nfields) =
+            match fields[1] {
+                "X" => (Event::Execute(try!(hex(2)), try!(hex(3))), 4),
+                "R" => {
+                    let e = Event::Load(try!(size()),
+                                        try!(hex(3)),
+                                        try!(hex(4)));
+                    (e, 5)
+                }
+                "W" => {
+                    let e = Event::Store(try!(size()),
+                                         try!(hex(3)),
+                                         try!(hex(4)));
+                    (e, 5)
+                }
+                "I" => {
+                    let status = try!(hex(2));
+
+                    if status > 0xffff {
+                        return Err(());
+                    }
+
+                    (Event::Irq(status as u16), 3)
+                }
+                _ => return Err(()),
+            };
+
+        if fields.len() != nfields {
+            return Err(());
+        }
+
+        Ok(Entry {
+            cycle: cycle,
+            event: event,
+        })
+    }
+}
+
+/// Options for `compare`
+#[derive(Clone, Copy, Debug)]
+pub struct CompareOptions {
+    /// Don't compare the cycle timestamps, only the sequence of
+    /// events. Useful when comparing against emulators using
+    /// different timings.
+    pub ignore_cycles: bool,
+    /// Ignore `X` events, only compare I/O and interrupts
+    pub ignore_instructions: bool,
+}
+
+/// First point where two logs differ
+#[derive(Debug)]
+pub struct Divergence {
+    /// Line number in the reference log (starting at 1)
+    pub line_a: usize,
+    /// Line number in the other log (starting at 1)
+    pub line_b: usize,
+    /// Entry in the reference log, `None` if it ended first
+    pub a: Option<Entry>,
+    /// Entry in the other log, `None` if it ended first
+    pub b: Option<Entry>,
+}
+
+/// Compare two logs and return the first divergence or `None` if
+/// they're identical.
+pub fn compare<A, B>(a: A,
+                     b: B,
+                     options: CompareOptions) -> io::Result<Option<Divergence>>
+    where A: BufRead, B: BufRead {
+    let mut a = Reader::new(a, options);
+    let mut b = Reader::new(b, options);
+
+    loop {
+        let ea = try!(a.next());
+        let eb = try!(b.next());
+
+        let same =
+            match (ea, eb) {
+                (None, None) => return Ok(None),
+                (Some(ea), Some(eb)) =>
+                    ea.event == eb.event &&
+                    (options.ignore_cycles || ea.cycle == eb.cycle),
+                _ => false,
+            };
+
+        if !same {
+            return Ok(Some(Divergence {
+                line_a: a.line,
+                line_b: b.line,
+                a: ea,
+                b: eb,
+            }));
+        }
+    }
+}
+
+/// Iterate over the entries of a log, skipping comments and
+/// filtered events
+struct Reader<R: BufRead> {
+    lines: io::Lines<R>,
+    /// Current line number
+    line: usize,
+    options: CompareOptions,
+}
+
+impl<R: BufRead> Reader<R> {
+    fn new(r: R, options: CompareOptions) -> Reader<R> {
+        Reader {
+            lines: r.lines(),
+            line: 0,
+            options: options,
+        }
+    }
+
+    fn next(&mut self) -> io::Result<Option<Entry>> {
+        while let Some(l) = self.lines.next() {
+            let l = try!(l);
+
+            self.line += 1;
+
+            let l = l.trim();
+
+            if l.is_empty() || l.starts_with('#') {
+                continue;
+            }
+
+            let entry =
+                match Entry::from_str(l) {
+                    Ok(e) => e,
+                    Err(_) => {
+                        let desc = format!("invalid event on line {}: {}",
+                                           self.line, l);
+
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  desc));
+                    }
+                };
+
+            if let Event::Execute(..) = entry.event {
+                if self.options.ignore_instructions {
+                    continue;
+                }
+            }
+
+            return Ok(Some(entry));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Returns true if an access to `addr` is considered an I/O access
+/// for the purpose of the event log
+pub fn is_io(addr: u32) -> bool {
+    use memory::map;
+
+    let abs_addr = map::mask_region(addr);
+
+    map::RAM.contains(abs_addr).is_none() &&
+        map::SCRATCH_PAD.contains(abs_addr).is_none() &&
+        map::BIOS.contains(abs_addr).is_none()
+}
+
+#[cfg(feature = "event_log")]
+mod logger {
+    use std::io::{Write, BufWriter};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+    use super::{Entry, Event};
+
+    /// Set while logging is active. Checked before taking the lock
+    /// since it's called for every single instruction.
+    static ENABLED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    lazy_static! {
+        static ref OUTPUT: Mutex<Option<BufWriter<Box<Write + Send>>>> = {
+            Mutex::new(None)
+        };
+    }
+
+    pub fn start(output: Box<Write + Send>) {
+        let mut out = OUTPUT.lock().unwrap();
+
+        *out = Some(BufWriter::new(output));
+
+        ENABLED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn stop() {
+        ENABLED.store(false, Ordering::SeqCst);
+
+        let mut out = OUTPUT.lock().unwrap();
+
+        if let Some(mut w) = out.take() {
+            if let Err(e) = w.flush() {
+                error!("Couldn't flush event log: {}", e);
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn log<F>(cycle: u64, f: F)
+        where F: FnOnce() -> Event {
+
+        if !enabled() {
+            return;
+        }
+
+        let mut out = OUTPUT.lock().unwrap();
+
+        let failed =
+            match *out {
+                Some(ref mut w) => {
+                    let entry = Entry {
+                        cycle: cycle,
+                        event: f(),
+                    };
+
+                    writeln!(w, "{}", entry).is_err()
+                }
+                None => false,
+            };
+
+        if failed {
+            error!("Couldn't write event log, stopping");
+            ENABLED.store(false, Ordering::SeqCst);
+            *out = None;
+        }
+    }
+}
+
+/// Start logging events to `output`, replacing any previous output
+#[cfg(feature = "event_log")]
+pub fn start(output: Box<::std::io::Write + Send>) {
+    logger::start(output)
+}
+
+#[cfg(not(feature = "event_log"))]
+pub fn start(_output: Box<::std::io::Write + Send>) {
+    warn!("Event log support is disabled, rebuild with the `event_log` \
+           feature");
+}
+
+/// Stop logging and flush the output
+#[cfg(feature = "event_log")]
+pub fn stop() {
+    logger::stop()
+}
+
+#[cfg(not(feature = "event_log"))]
+pub fn stop() {
+    // NOP
+}
+
+/// Log the event returned by `f`. `f` is only called if logging is
+/// active.
+#[cfg(feature = "event_log")]
+#[inline(always)]
+pub fn log<F>(cycle: u64, f: F)
+    where F: FnOnce() -> Event {
+    logger::log(cycle, f)
+}
+
+#[cfg(not(feature = "event_log"))]
+#[inline(always)]
+pub fn log<F>(_cycle: u64, _f: F)
+    where F: FnOnce() -> Event {
+    // NOP
+}
+
+/// Log the event returned by `f` if logging is active and `addr` is
+/// an I/O address
+#[cfg(feature = "event_log")]
+#[inline(always)]
+pub fn log_io<F>(cycle: u64, addr: u32, f: F)
+    where F: FnOnce() -> Event {
+    if logger::enabled() && is_io(addr) {
+        logger::log(cycle, f)
+    }
+}
+
+#[cfg(not(feature = "event_log"))]
+#[inline(always)]
+pub fn log_io<F>(_cycle: u64, _addr: u32, _f: F)
+    where F: FnOnce() -> Event {
+    // NOP
+}
+
+#[test]
+fn entry_round_trip() {
+    let entries = [
+        Entry { cycle: 0, event: Event::Execute(0xbfc00000, 0x3c080013) },
+        Entry { cycle: 1280, event: Event::Store(4, 0x1f801010, 0x13243f) },
+        Entry { cycle: 1300, event: Event::Load(2, 0x1f801814, 0xffff) },
+        Entry { cycle: 5678, event: Event::Load(1, 0x1f801800, 0x18) },
+        Entry { cycle: 9999, event: Event::Irq(0x0001) },
+    ];
+
+    for e in &entries {
+        let s = e.to_string();
+
+        assert!(Entry::from_str(&s) == Ok(*e));
+    }
+
+    assert!(entries[1].to_string() == "1280 W 4 1f801010 0013243f");
+
+    let invalid = [
+        "",
+        "1234",
+        "1234 X bfc00000",
+        "1234 X bfc00000 3c080013 0",
+        "1234 R 3 1f801810 0",
+        "1234 Q 0",
+        "1234 I 10000",
+        "-1 I 0",
+    ];
+
+    for s in &invalid {
+        assert!(Entry::from_str(s).is_err());
+    }
+}
+
+#[test]
+fn compare_logs() {
+    let options = CompareOptions {
+        ignore_cycles: false,
+        ignore_instructions: false,
+    };
+
+    let a = b"# Reference\n\
+              10 X bfc00000 00000000\n\
+              12 W 4 1f801810 e1000000\n\
+              20 I 0001\n";
+
+    // Same events, different comments and timings
+    let b = b"10 X bfc00000 00000000\n\
+              \n\
+              13 W 4 1f801810 e1000000\n\
+              20 I 0001\n";
+
+    let d = compare(&a[..], &b[..], options).unwrap().unwrap();
+
+    assert!(d.line_a == 3);
+    assert!(d.line_b == 3);
+    assert!(d.a.unwrap().cycle == 12);
+    assert!(d.b.unwrap().cycle == 13);
+
+    let ignore_cycles = CompareOptions {
+        ignore_cycles: true,
+        ..options
+    };
+
+    assert!(compare(&a[..], &b[..], ignore_cycles).unwrap().is_none());
+
+    // Missing instruction and truncated log
+    let c = b"12 W 4 1f801810 e1000000\n";
+
+    let ignore_instructions = CompareOptions {
+        ignore_instructions: true,
+        ..options
+    };
+
+    let d = compare(&a[..], &c[..], ignore_instructions).unwrap().unwrap();
+
+    assert!(d.line_a == 4);
+    assert!(d.a == Some(Entry { cycle: 20, event: Event::Irq(1) }));
+    assert!(d.b.is_none());
+
+    // Invalid entries are reported as errors
+    assert!(compare(&a[..], &b"10 Z\n"[..], options).is_err());
+}
@@ -5,6 +5,7 @@ use memory::timers::Timers;
 use shared::SharedState;
 use interrupt::Interrupt;
 use timekeeper::{Peripheral, Cycles, FracCycles};
+use tracer::module_tracer;
 
 use self::renderer::{Renderer, Vertex, PrimitiveAttributes};
 use self::renderer::{BlendMode, SemiTransparencyMode, TextureDepth};
@@ -285,7 +286,7 @@ impl Gpu {
 
         if !self.vblank_interrupt && vblank_interrupt {
             // Rising edge of the vblank interrupt
-            shared.irq_state_mut().assert(Interrupt::VBlank);
+            shared.assert_irq(Interrupt::VBlank);
         }
 
         if self.vblank_interrupt && !vblank_interrupt {
@@ -295,6 +296,13 @@ impl Gpu {
 
         self.vblank_interrupt = vblank_interrupt;
 
+        module_tracer("GPU", |m| {
+            let now = shared.tk().now();
+
+            m.trace(now, "vblank", vblank_interrupt);
+            m.trace(now, "display_line", self.display_line);
+        });
+
         self.predict_next_sync(shared);
     }
 
@@ -411,14 +419,22 @@ impl Gpu {
         self.sync(shared);
 
         match offset {
-            0 => self.gp0(renderer, val),
+            0 => self.gp0(shared, renderer, val),
             4 => self.gp1(shared, renderer, val, timers),
             _ => unreachable!(),
         }
     }
 
-    /// Dispatch to the current GP0 handler method
-    pub fn gp0(&mut self, renderer: &mut Renderer, val: u32) {
+    /// Dispatch to the current GP0 handler method. Used for both CPU
+    /// and DMA writes.
+    pub fn gp0(&mut self,
+               shared: &mut SharedState,
+               renderer: &mut Renderer,
+               val: u32) {
+        module_tracer("GPU", |m| {
+            m.event(shared.tk().now(), "gp0", val);
+        });
+
         (self.gp0_handler)(self, renderer, val);
     }
 
@@ -658,7 +674,8 @@ impl Gpu {
                 0xe4 => (1,  Gpu::gp0_drawing_area_bottom_right, false),
                 0xe5 => (1,  Gpu::gp0_drawing_offset, false),
                 0xe6 => (1,  Gpu::gp0_mask_bit_setting, false),
-                _    => panic!("Unhandled GP0 command {:08x}", gp0),
+                _    => unhandled!((1, Gpu::gp0_nop, false),
+                                   "Unhandled GP0 command {:08x}", gp0),
             };
 
         let textured = opcode & 0x4 != 0;
@@ -1230,6 +1247,10 @@ impl Gpu {
                val: u32,
                timers: &mut Timers) {
 
+        module_tracer("GPU", |m| {
+            m.event(shared.tk().now(), "gp1", val);
+        });
+
         let opcode = (val >> 24) & 0xff;
 
         match opcode {
@@ -22,6 +22,36 @@ pub trait Renderer {
                   pixel_buffer: &[u16]);
 }
 
+/// Renderer discarding everything, used to run the emulator headless
+/// (benchmarks, fuzzing...)
+pub struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn set_draw_offset(&mut self, _: i16, _: i16) {
+    }
+
+    fn set_draw_area(&mut self, _: (u16, u16), _: (u16, u16)) {
+    }
+
+    fn set_display_mode(&mut self, _: (u16, u16), _: (u16, u16), _: bool) {
+    }
+
+    fn push_line(&mut self, _: &PrimitiveAttributes, _: &[Vertex; 2]) {
+    }
+
+    fn push_triangle(&mut self, _: &PrimitiveAttributes, _: &[Vertex; 3]) {
+    }
+
+    fn push_quad(&mut self, _: &PrimitiveAttributes, _: &[Vertex; 4]) {
+    }
+
+    fn fill_rect(&mut self, _: [u8; 3], _: (u16, u16), _: (u16, u16)) {
+    }
+
+    fn load_image(&mut self, _: (u16, u16), _: (u16, u16), _: &[u16]) {
+    }
+}
+
 pub struct Vertex {
     pub position: [i16; 2],
     pub color: [u8; 3],
@@ -16,6 +16,8 @@ pub enum Interrupt {
     Timer2 = 6,
     /// Gamepad and Memory Card controller interrupt
     PadMemCard = 7,
+    /// Second serial port (SIO1)
+    Sio = 8,
 }
 
 #[derive(Clone, Copy, RustcDecodable, RustcEncodable)]
@@ -63,7 +65,8 @@ impl InterruptState {
                           Interrupt::Timer0,
                           Interrupt::Timer1,
                           Interrupt::Timer2,
-                          Interrupt::PadMemCard];
+                          Interrupt::PadMemCard,
+                          Interrupt::Sio];
 
         let rem = supported.iter().fold(mask,
                                         |mask, &it| mask & !(1 << it as u16));
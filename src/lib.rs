@@ -5,13 +5,15 @@ extern crate cdimage;
 extern crate arrayvec;
 extern crate rustc_serialize;
 
-#[cfg(feature = "trace")]
+#[cfg(any(feature = "trace", feature = "event_log"))]
 #[macro_use]
 extern crate lazy_static;
 
 #[macro_use]
 mod box_array;
 #[macro_use]
+mod unhandled;
+#[macro_use]
 mod serializer;
 #[macro_use]
 pub mod tracer;
@@ -27,11 +29,14 @@ pub mod debugger;
 pub mod assembler;
 pub mod parallel_io;
 pub mod debug_uart;
+pub mod sio1;
+pub mod event_log;
 
 mod interrupt;
 mod timekeeper;
 mod spu;
 mod mdec;
+mod tcp_port;
 
 mod version {
     // VERSION and VERSION_CSTR are generated by build.rs
@@ -40,3 +45,7 @@ mod version {
 
 pub use version::VERSION;
 pub use version::VERSION_CSTR;
+
+/// The MDEC is only reachable through the interconnect, it's exported
+/// on its own for the benchmarks
+pub use mdec::MDec;
@@ -93,7 +93,7 @@ impl Dma {
 
         if !prev_irq && self.irq() {
             // Rising edge of the done interrupt
-            shared.irq_state_mut().assert(Interrupt::Dma);
+            shared.assert_irq(Interrupt::Dma);
         }
     }
 
@@ -122,7 +122,7 @@ impl Dma {
 
         if !prev_irq && self.irq() {
             // Rising edge of the done interrupt
-            shared.irq_state_mut().assert(Interrupt::Dma);
+            shared.assert_irq(Interrupt::Dma);
         }
     }
 }
@@ -18,6 +18,7 @@ use padmemcard::PadMemCard;
 use mdec::MDec;
 use parallel_io::ParallelIo;
 use debug_uart::DebugUart;
+use sio1::Sio1;
 use tracer::module_tracer;
 
 /// Global interconnect
@@ -54,12 +55,18 @@ pub struct Interconnect {
     parallel_io: ParallelIo,
     /// Debug UART
     debug_uart: DebugUart,
+    /// Second serial port
+    sio1: Sio1,
 }
 
 impl Interconnect {
     pub fn new(bios: Bios,
                gpu: Gpu,
                disc: Option<Disc>) -> Interconnect {
+        if let Some(ref disc) = disc {
+            check_region(&bios, disc);
+        }
+
         Interconnect {
             bios: bios,
             ram: Ram::new(),
@@ -76,6 +83,7 @@ impl Interconnect {
             mem_control: [0; 9],
             parallel_io: ParallelIo::disconnected(),
             debug_uart: DebugUart::new(),
+            sio1: Sio1::new(),
         }
     }
 
@@ -93,6 +101,10 @@ impl Interconnect {
         if shared.tk().needs_sync(Peripheral::CdRom) {
             self.cdrom.sync(shared);
         }
+
+        if shared.tk().needs_sync(Peripheral::Sio1) {
+            self.sio1.sync(shared);
+        }
     }
 
     pub fn cache_control(&self) -> CacheControl {
@@ -145,6 +157,11 @@ impl Interconnect {
         &mut self.parallel_io
     }
 
+    /// Return a mutable reference to the second serial port
+    pub fn sio1_mut(&mut self) -> &mut Sio1 {
+        &mut self.sio1
+    }
+
     /// Interconnect: load instruction at `PC`. Only the RAM and BIOS
     /// are supported, would it make sense to fetch instructions from
     /// anything else?
@@ -233,6 +250,10 @@ impl Interconnect {
             return self.pad_memcard.load::<A>(shared, offset);
         }
 
+        if let Some(offset) = map::SIO1.contains(abs_addr) {
+            return self.sio1.load::<A>(shared, offset);
+        }
+
         if let Some(offset) = map::EXPANSION_1.contains(abs_addr) {
             return self.parallel_io.load::<A>(shared, offset);
         }
@@ -295,6 +316,8 @@ impl Interconnect {
                 4 => shared.irq_state_mut().set_mask(val as u16),
                 _ => panic!("Unhandled IRQ store at address {:08x}"),
             }
+
+            shared.trace_irq();
             return;
         }
 
@@ -338,6 +361,11 @@ impl Interconnect {
             return;
         }
 
+        if let Some(offset) = map::SIO1.contains(abs_addr) {
+            self.sio1.store::<A>(shared, offset, val);
+            return;
+        }
+
         if let Some(_) = map::CACHE_CONTROL.contains(abs_addr) {
             if A::size() != 4 {
                 panic!("Unhandled cache control access");
@@ -388,6 +416,11 @@ impl Interconnect {
             return;
         }
 
+        if let Some(offset) = map::EXPANSION_1.contains(abs_addr) {
+            self.parallel_io.store::<A>(shared, offset, val);
+            return;
+        }
+
         if let Some(offset) = map::EXPANSION_2.contains(abs_addr) {
             self.debug_uart.store::<A>(shared, offset, val);
             return;
@@ -519,7 +552,8 @@ impl Interconnect {
         });
 
         match sync {
-                Sync::LinkedList => self.do_dma_linked_list(renderer, port),
+                Sync::LinkedList =>
+                    self.do_dma_linked_list(shared, renderer, port),
                 _                => self.do_dma_block(shared, renderer, port),
         }
 
@@ -527,7 +561,10 @@ impl Interconnect {
     }
 
     /// Emulate DMA transfer for linked list synchronization mode.
-    fn do_dma_linked_list(&mut self, renderer: &mut Renderer, port: Port) {
+    fn do_dma_linked_list(&mut self,
+                          shared: &mut SharedState,
+                          renderer: &mut Renderer,
+                          port: Port) {
         let channel = self.dma.channel_mut(port);
 
         let mut addr = channel.base() & 0x1ffffc;
@@ -556,7 +593,7 @@ impl Interconnect {
                 let command = self.ram.load::<Word>(addr);
 
                 // Send command to the GPU
-                self.gpu.gp0(renderer, command);
+                self.gpu.gp0(shared, renderer, command);
 
                 remsz -= 1;
             }
@@ -610,7 +647,7 @@ impl Interconnect {
                     let src_word = self.ram.load::<Word>(cur_addr);
 
                     match port {
-                        Port::Gpu => self.gpu.gp0(renderer, src_word),
+                        Port::Gpu => self.gpu.gp0(shared, renderer, src_word),
                         Port::MDecIn => self.mdec.command(shared, src_word),
                         // XXX ignre transfers to the SPU for now
                         Port::Spu => (),
@@ -650,6 +687,19 @@ impl Interconnect {
     }
 }
 
+/// Log a warning if `disc` is not from the same region as `bios`,
+/// otherwise the resulting hang at the logo screen can be pretty
+/// confusing.
+fn check_region(bios: &Bios, disc: &Disc) {
+    let disc_region = disc.region();
+
+    if !bios.matches_region(disc_region) {
+        warn!("Region mismatch: disc {} is {:?} but the BIOS is {:?}, \
+               the game will probably not boot",
+              disc.serial_number(), disc_region, bios.metadata());
+    }
+}
+
 #[derive(Clone,Copy, RustcDecodable, RustcEncodable)]
 pub struct CacheControl(u32);
 
@@ -750,7 +800,10 @@ pub mod map {
     pub const MEM_CONTROL: Range = Range(0x1f801000, 36);
 
     /// Gamepad and memory card controller
-    pub const PAD_MEMCARD: Range = Range(0x1f801040, 32);
+    pub const PAD_MEMCARD: Range = Range(0x1f801040, 16);
+
+    /// Second serial port
+    pub const SIO1: Range = Range(0x1f801050, 16);
 
     /// Register that has something to do with RAM configuration,
     /// configured by the BIOS
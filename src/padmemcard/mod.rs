@@ -193,9 +193,7 @@ impl PadMemCard {
                         if self.dsr_it {
                             if !self.interrupt {
                                 // Rising edge of the interrupt
-                                let irq_state = shared.irq_state_mut();
-
-                                irq_state.assert(Interrupt::PadMemCard);
+                                shared.assert_irq(Interrupt::PadMemCard);
                             }
 
                             self.interrupt = true;
@@ -351,7 +349,7 @@ impl PadMemCard {
                     warn!("Gamepad interrupt acknowledge while DSR is active");
 
                     self.interrupt = true;
-                    shared.irq_state_mut().assert(Interrupt::PadMemCard);
+                    shared.assert_irq(Interrupt::PadMemCard);
                 }
             }
 
@@ -0,0 +1,107 @@
+//! Emulation of the Action Replay/GameShark style cheat cartridges
+//! connected to the parallel port.
+//!
+//! The cartridge firmware is mapped at the start of EXPANSION 1 and
+//! uses the pre-boot hook (see the `expansion_rom` module) to take
+//! over the boot sequence and display its menu. The I/O ports are
+//! mapped as follows (per the No$ spec):
+//!
+//! * 0x1f020010 (W): Comms link STB pin (bit 0)
+//! * 0x1f020018 (R): Switch setting (bit 0: 0=off, 1=on)
+//! * 0x1f060000 (R): Comms link data in
+//! * 0x1f060008 (W): Comms link data out
+//!
+//! Only these registers are emulated. The comms link is a raw byte
+//! pipe (see the `comms_link` module for its limitations), none of
+//! the Caetla/PAR host protocol is implemented.
+//!
+//! Only cartridges whose firmware fits in EXPANSION 1 without bank
+//! switching are supported: the Xplorer/Xploder layout isn't
+//! documented well enough to be emulated. Flash programming is not
+//! emulated, writes to the ROM are ignored.
+
+use std::path::Path;
+
+use shared::SharedState;
+
+use super::ParallelIoModule;
+use super::expansion_rom::{ExpansionRom, Error};
+use super::comms_link::{CommsLink, CommsPort};
+
+pub struct CheatCartridge {
+    /// Cartridge firmware
+    rom: ExpansionRom,
+    /// State of the switch on the cartridge. When it's off the
+    /// firmware lets the game run without applying the cheat codes.
+    switch: bool,
+    /// Comms link port
+    comms: CommsPort,
+}
+
+impl CheatCartridge {
+    /// Create a new cartridge using `rom` as firmware. The switch is
+    /// initially on.
+    pub fn new(rom: ExpansionRom) -> CheatCartridge {
+        CheatCartridge {
+            rom: rom,
+            switch: true,
+            comms: CommsPort::new(),
+        }
+    }
+
+    /// Load the cartridge firmware from a ROM dump
+    pub fn load_file(path: &Path) -> Result<CheatCartridge, Error> {
+        let rom = try!(ExpansionRom::load_file(path));
+
+        if !rom.has_preboot_hook() {
+            warn!("Cheat cartridge ROM doesn't have a pre-boot hook, \
+                   is it a valid dump?");
+        }
+
+        Ok(CheatCartridge::new(rom))
+    }
+
+    pub fn switch(&self) -> bool {
+        self.switch
+    }
+
+    /// Flip the switch on the cartridge
+    pub fn set_switch(&mut self, on: bool) {
+        self.switch = on;
+    }
+
+    /// Plug `link` into the cartridge's comms link port
+    pub fn set_comms_link(&mut self, link: Box<CommsLink>) {
+        self.comms.set_link(link);
+    }
+}
+
+impl ParallelIoModule for CheatCartridge {
+    fn load(&mut self, shared: &mut SharedState, offset: u32) -> u8 {
+        match offset {
+            SWITCH_OFFSET => self.switch as u8,
+            COMMS_DATA_IN_OFFSET => self.comms.data_in().unwrap_or(0),
+            _ => self.rom.load(shared, offset),
+        }
+    }
+
+    fn store(&mut self, shared: &mut SharedState, offset: u32, val: u8) {
+        match offset {
+            COMMS_STROBE_OFFSET => self.comms.set_strobe((val & 1) != 0),
+            COMMS_DATA_OUT_OFFSET => self.comms.send(val),
+            _ => self.rom.store(shared, offset, val),
+        }
+    }
+}
+
+/// Offset of the comms link STB pin register
+const COMMS_STROBE_OFFSET: u32 = 0x20010;
+
+/// Offset of the switch setting register
+const SWITCH_OFFSET: u32 = 0x20018;
+
+/// Offset of the comms link data input register
+const COMMS_DATA_IN_OFFSET: u32 = 0x60000;
+
+/// Offset of the comms link data output register
+const COMMS_DATA_OUT_OFFSET: u32 = 0x60008;
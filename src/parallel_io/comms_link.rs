@@ -0,0 +1,136 @@
+//! Backends for the cheat cartridge "comms link", the parallel cable
+//! used to connect the cartridge to a PC. On real hardware this is
+//! how Caetla-style firmwares exchange data with homebrew tools
+//! running on the PC.
+//!
+//! Only the data registers are emulated, as a raw byte pipe: bytes
+//! written by the console are forwarded to the backend immediately
+//! and each byte received from the backend stays in the data in
+//! register until the console pulses its STB pin. The PC side of the
+//! handshake (the host's strobe and acknowledge lines) isn't
+//! documented well enough to be emulated, so nothing tells the
+//! firmware that a new byte is available.
+//!
+//! As a consequence the Caetla/PAR protocols are *not* supported:
+//! the emulator is not a drop-in target for catflap-style exe upload
+//! or memory peek/poke tools, which would need both the full
+//! handshake and a bridge from the PC's parallel port to the TCP
+//! backend. The backends are only useful for custom software which
+//! polls the data in register.
+
+use std::io;
+use std::net::ToSocketAddrs;
+
+use tcp_port::TcpPort;
+
+/// Interface to the PC end of the comms link
+pub trait CommsLink {
+    /// Send a byte to the PC
+    fn send(&mut self, b: u8);
+
+    /// Return the next byte sent by the PC or `None` if there's
+    /// nothing pending. Must not block.
+    fn receive(&mut self) -> Option<u8>;
+}
+
+/// Cartridge end of the comms link: a data in latch, a data out
+/// register and the STB handshake pin
+pub struct CommsPort {
+    /// Comms link backend
+    link: Box<CommsLink>,
+    /// Byte received from the host, waiting to be acknowledged by
+    /// the firmware
+    data_in: Option<u8>,
+    /// Current level of our STB pin
+    strobe: bool,
+}
+
+impl CommsPort {
+    pub fn new() -> CommsPort {
+        CommsPort {
+            link: Box::new(Disconnected),
+            data_in: None,
+            strobe: false,
+        }
+    }
+
+    /// Plug `link` into the port
+    pub fn set_link(&mut self, link: Box<CommsLink>) {
+        self.link = link;
+        self.data_in = None;
+    }
+
+    /// Return the pending byte from the host, fetching a new one from
+    /// the link if necessary
+    pub fn data_in(&mut self) -> Option<u8> {
+        if self.data_in.is_none() {
+            self.data_in = self.link.receive();
+        }
+
+        self.data_in
+    }
+
+    /// Set the level of our STB pin. A rising edge acknowledges the
+    /// current data in byte.
+    pub fn set_strobe(&mut self, strobe: bool) {
+        if strobe && !self.strobe {
+            self.data_in = None;
+        }
+
+        self.strobe = strobe;
+    }
+
+    /// Send a byte to the host
+    pub fn send(&mut self, b: u8) {
+        self.link.send(b);
+    }
+}
+
+/// Dummy implementation of `CommsLink` when no cable is plugged in
+pub struct Disconnected;
+
+impl CommsLink for Disconnected {
+    fn send(&mut self, _: u8) {
+        // NOP
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+/// Comms link bridged over a raw TCP connection: every byte sent by
+/// the console is written to the socket as-is and every byte read
+/// from the socket is presented to the console.
+pub struct TcpCommsLink {
+    /// Connection to the host
+    port: TcpPort,
+}
+
+impl TcpCommsLink {
+    /// Listen for host connections on `addr`. This doesn't block, the
+    /// connection is accepted when the emulator polls the link. If
+    /// the host disconnects a new connection can be made later on.
+    pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<TcpCommsLink> {
+        let port = try!(TcpPort::listen("Comms link", addr, true));
+
+        Ok(TcpCommsLink {
+            port: port,
+        })
+    }
+}
+
+impl CommsLink for TcpCommsLink {
+    fn send(&mut self, b: u8) {
+        self.port.send(&[b]);
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        let mut buf = [0];
+
+        match self.port.receive(&mut buf) {
+            0 => None,
+            _ => Some(buf[0]),
+        }
+    }
+}
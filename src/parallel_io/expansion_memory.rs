@@ -0,0 +1,474 @@
+//! Parallel I/O module mapping a writable memory (battery-backed RAM
+//! or flash) in the EXPANSION 1 region. The contents are backed by a
+//! file on disk so they persist between sessions, which is what some
+//! homebrew and dev cartridge software expects.
+//!
+//! Modified images are saved when the module is dropped and whenever
+//! the frontend calls `ParallelIo::flush`. The new contents are
+//! written to a temporary file which then replaces the image, so the
+//! previous image survives if the emulator dies in the middle of a
+//! save.
+
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+use shared::SharedState;
+
+use super::ParallelIoModule;
+
+/// Type of memory mapped in the expansion region
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Battery-backed SRAM, writes go straight to the memory
+    Sram,
+    /// JEDEC flash using the standard AMD command set. Only the
+    /// 128KB Am29F010 is emulated: bytes must be programmed using the
+    /// unlock sequence and can only be set back to 0xff by erasing a
+    /// sector.
+    Flash,
+}
+
+pub struct ExpansionMemory {
+    kind: Kind,
+    /// Memory contents, mapped at the beginning of EXPANSION 1
+    data: Vec<u8>,
+    /// File used to persist `data`
+    path: PathBuf,
+    /// True if `data` has been modified since the last save
+    dirty: bool,
+    /// Flash command state machine
+    flash_state: FlashState,
+}
+
+impl ExpansionMemory {
+    /// Open the memory image at `path`. If the file doesn't exist a
+    /// blank image of `size` bytes is created. Otherwise the file must
+    /// be exactly `size` bytes long. Flash images must be
+    /// `FLASH_SIZE` bytes long.
+    pub fn open(path: &Path,
+                kind: Kind,
+                size: usize) -> Result<ExpansionMemory, Error> {
+        if size > EXPANSION_1_SIZE {
+            return Err(Error::TooBig(size));
+        }
+
+        if kind == Kind::Flash && size != FLASH_SIZE {
+            return Err(Error::BadFlashSize(size));
+        }
+
+        // Newly created images are marked dirty so that they get
+        // written to disk
+        let (data, dirty) =
+            match File::open(path) {
+                Ok(f) => {
+                    let mut data = Vec::with_capacity(size);
+
+                    // Read one more byte than expected to catch
+                    // oversized images
+                    try!(f.take(size as u64 + 1).read_to_end(&mut data));
+
+                    if data.len() != size {
+                        return Err(Error::BadSize(data.len()));
+                    }
+
+                    (data, false)
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                    info!("Creating new expansion memory image {}",
+                          path.display());
+
+                    let blank =
+                        match kind {
+                            Kind::Sram => 0,
+                            Kind::Flash => 0xff,
+                        };
+
+                    (vec![blank; size], true)
+                }
+                Err(e) => return Err(Error::IoError(e)),
+            };
+
+        Ok(ExpansionMemory {
+            kind: kind,
+            data: data,
+            path: path.to_path_buf(),
+            dirty: dirty,
+            flash_state: FlashState::Read,
+        })
+    }
+
+    /// Write the memory contents back to disk if they've been
+    /// modified
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        {
+            let mut f = try!(File::create(&tmp_path));
+
+            try!(f.write_all(&self.data));
+            try!(f.sync_all());
+        }
+
+        try!(fs::rename(&tmp_path, &self.path));
+
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, val: u8) {
+        if let Some(b) = self.data.get_mut(offset) {
+            if *b != val {
+                *b = val;
+                self.dirty = true;
+            }
+        }
+    }
+
+    fn flash_store(&mut self, offset: u32, val: u8) {
+        let offset = offset as usize;
+        // Unlock addresses only decode the low 15 bits
+        let cmd_addr = offset & 0x7fff;
+
+        let next =
+            match (self.flash_state, cmd_addr, val) {
+                (FlashState::Program, _, _) => {
+                    // Programming can only clear bits
+                    let cur = *self.data.get(offset).unwrap_or(&0xff);
+
+                    self.write(offset, cur & val);
+
+                    FlashState::Read
+                }
+                // Reset can be issued at any other time
+                (_, _, 0xf0) => FlashState::Read,
+                (FlashState::Read, 0x5555, 0xaa) |
+                (FlashState::AutoSelect, 0x5555, 0xaa) =>
+                    FlashState::Unlock1,
+                (FlashState::Unlock1, 0x2aaa, 0x55) => FlashState::Unlock2,
+                (FlashState::Unlock2, 0x5555, 0xa0) => FlashState::Program,
+                (FlashState::Unlock2, 0x5555, 0x80) => FlashState::EraseSetup,
+                (FlashState::Unlock2, 0x5555, 0x90) => FlashState::AutoSelect,
+                (FlashState::EraseSetup, 0x5555, 0xaa) =>
+                    FlashState::EraseUnlock1,
+                (FlashState::EraseUnlock1, 0x2aaa, 0x55) =>
+                    FlashState::EraseUnlock2,
+                (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                    // Chip erase
+                    for i in 0..self.data.len() {
+                        self.write(i, 0xff);
+                    }
+
+                    FlashState::Read
+                }
+                (FlashState::EraseUnlock2, _, 0x30) => {
+                    // Sector erase
+                    let start = offset & !(FLASH_SECTOR_SIZE - 1);
+                    let end = start + FLASH_SECTOR_SIZE;
+
+                    for i in start..end {
+                        self.write(i, 0xff);
+                    }
+
+                    FlashState::Read
+                }
+                (state, _, _) => {
+                    warn!("Unexpected flash write in state {:?}: \
+                           0x{:x} 0x{:02x}",
+                          state, offset, val);
+
+                    FlashState::Read
+                }
+            };
+
+        self.flash_state = next;
+    }
+}
+
+impl ParallelIoModule for ExpansionMemory {
+    fn load(&mut self, _: &mut SharedState, offset: u32) -> u8 {
+        if self.flash_state == FlashState::AutoSelect {
+            return match offset & 0xff {
+                0 => FLASH_MANUFACTURER_ID,
+                1 => FLASH_DEVICE_ID,
+                _ => 0,
+            };
+        }
+
+        // Past the end of the memory the bus is floating
+        *self.data.get(offset as usize).unwrap_or(&!0)
+    }
+
+    fn store(&mut self, _: &mut SharedState, offset: u32, val: u8) {
+        match self.kind {
+            Kind::Sram => self.write(offset as usize, val),
+            Kind::Flash => self.flash_store(offset, val),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.save()
+    }
+}
+
+impl Drop for ExpansionMemory {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            error!("Couldn't save expansion memory to {}: {}",
+                   self.path.display(), e);
+        }
+    }
+}
+
+/// State of the flash command decoder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FlashState {
+    /// Normal read mode
+    Read,
+    /// Received 0xaa at 0x5555
+    Unlock1,
+    /// Received 0x55 at 0x2aaa
+    Unlock2,
+    /// Next write programs a byte
+    Program,
+    /// Received the erase command, waiting for the second unlock
+    /// sequence
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+    /// Reads return the chip IDs
+    AutoSelect,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error while reading the memory image
+    IoError(io::Error),
+    /// The memory doesn't fit in the EXPANSION 1 region
+    TooBig(usize),
+    /// The memory image on disk doesn't have the expected size
+    BadSize(usize),
+    /// Flash memories must be `FLASH_SIZE` bytes long
+    BadFlashSize(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::IoError(err)
+    }
+}
+
+/// Size of the Am29F010
+pub const FLASH_SIZE: usize = 128 * 1024;
+
+/// Sector size of the Am29F010
+const FLASH_SECTOR_SIZE: usize = 16 * 1024;
+
+/// Manufacturer ID returned in autoselect mode (AMD)
+const FLASH_MANUFACTURER_ID: u8 = 0x01;
+
+/// Device ID returned in autoselect mode (Am29F010)
+const FLASH_DEVICE_ID: u8 = 0x20;
+
+/// Size of the EXPANSION 1 memory region
+const EXPANSION_1_SIZE: usize = ::memory::map::EXPANSION_1.1 as usize;
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use shared::SharedState;
+
+    use parallel_io::ParallelIoModule;
+
+    use super::{ExpansionMemory, Kind, Error, FLASH_SIZE};
+
+    /// Wrapper removing the image file once the test is done
+    struct TestMemory {
+        mem: Option<ExpansionMemory>,
+        path: PathBuf,
+        shared: SharedState,
+    }
+
+    impl TestMemory {
+        fn new(name: &str, kind: Kind, size: usize) -> TestMemory {
+            let file = format!("rustation-test-{}.bin", name);
+            let path = env::temp_dir().join(file);
+
+            let _ = fs::remove_file(&path);
+
+            let mem =
+                match ExpansionMemory::open(&path, kind, size) {
+                    Ok(m) => m,
+                    Err(e) => panic!("Can't open {}: {:?}",
+                                     path.display(), e),
+                };
+
+            TestMemory {
+                mem: Some(mem),
+                path: path,
+                shared: SharedState::new(),
+            }
+        }
+
+        fn load(&mut self, offset: u32) -> u8 {
+            self.mem.as_mut().unwrap().load(&mut self.shared, offset)
+        }
+
+        fn store(&mut self, offset: u32, val: u8) {
+            self.mem.as_mut().unwrap().store(&mut self.shared, offset, val)
+        }
+
+        /// Send an unlocked flash command
+        fn command(&mut self, cmd: u8) {
+            self.store(0x5555, 0xaa);
+            self.store(0x2aaa, 0x55);
+            self.store(0x5555, cmd);
+        }
+
+        fn program(&mut self, offset: u32, val: u8) {
+            self.command(0xa0);
+            self.store(offset, val);
+        }
+    }
+
+    impl Drop for TestMemory {
+        fn drop(&mut self) {
+            // Drop the memory first since it saves the image
+            self.mem = None;
+
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn flash_program() {
+        let mut m = TestMemory::new("program", Kind::Flash, FLASH_SIZE);
+
+        assert_eq!(m.load(0x100), 0xff);
+
+        // Writes without the unlock sequence are ignored
+        m.store(0x100, 0x00);
+        assert_eq!(m.load(0x100), 0xff);
+
+        m.program(0x100, 0x0f);
+        assert_eq!(m.load(0x100), 0x0f);
+
+        // Programming can only clear bits
+        m.program(0x100, 0xf3);
+        assert_eq!(m.load(0x100), 0x03);
+
+        // Only one byte per program command
+        m.store(0x101, 0x00);
+        assert_eq!(m.load(0x101), 0xff);
+
+        // The unlock addresses only decode the low 15 bits
+        m.store(0xd555, 0xaa);
+        m.store(0x1aaaa, 0x55);
+        m.store(0x5555, 0xa0);
+        m.store(0x1f000, 0x12);
+        assert_eq!(m.load(0x1f000), 0x12);
+    }
+
+    #[test]
+    fn flash_sector_erase() {
+        let mut m = TestMemory::new("sector", Kind::Flash, FLASH_SIZE);
+
+        m.program(0x3fff, 0x00);
+        m.program(0x4000, 0x00);
+        m.program(0x7fff, 0x00);
+        m.program(0x8000, 0x00);
+
+        m.command(0x80);
+        m.store(0x5555, 0xaa);
+        m.store(0x2aaa, 0x55);
+        // Any address within the sector
+        m.store(0x4567, 0x30);
+
+        assert_eq!(m.load(0x3fff), 0x00);
+        assert_eq!(m.load(0x4000), 0xff);
+        assert_eq!(m.load(0x7fff), 0xff);
+        assert_eq!(m.load(0x8000), 0x00);
+    }
+
+    #[test]
+    fn flash_chip_erase() {
+        let mut m = TestMemory::new("chip", Kind::Flash, FLASH_SIZE);
+
+        m.program(0x0, 0x00);
+        m.program(0x1ffff, 0x00);
+
+        m.command(0x80);
+        m.command(0x10);
+
+        assert_eq!(m.load(0x0), 0xff);
+        assert_eq!(m.load(0x1ffff), 0xff);
+    }
+
+    #[test]
+    fn flash_autoselect_and_reset() {
+        let mut m = TestMemory::new("autoselect", Kind::Flash, FLASH_SIZE);
+
+        m.program(0x0, 0x42);
+
+        m.command(0x90);
+        assert_eq!(m.load(0x0), 0x01);
+        assert_eq!(m.load(0x1), 0x20);
+        // IDs are mirrored every 256 bytes
+        assert_eq!(m.load(0x201), 0x20);
+
+        m.store(0x0, 0xf0);
+        assert_eq!(m.load(0x0), 0x42);
+
+        // Reset in the middle of an unlock sequence
+        m.store(0x5555, 0xaa);
+        m.store(0x2aaa, 0x55);
+        m.store(0x0, 0xf0);
+        m.store(0x5555, 0xa0);
+        m.store(0x10, 0x00);
+        assert_eq!(m.load(0x10), 0xff);
+    }
+
+    #[test]
+    fn flash_size() {
+        let path = env::temp_dir().join("rustation-test-never-created.bin");
+
+        match ExpansionMemory::open(&path, Kind::Flash, 512 * 1024) {
+            Err(Error::BadFlashSize(s)) => assert_eq!(s, 512 * 1024),
+            Err(e) => panic!("Unexpected error {:?}", e),
+            Ok(_) => panic!("Unsupported flash size accepted"),
+        }
+    }
+
+    #[test]
+    fn sram_save() {
+        let mut m = TestMemory::new("save", Kind::Sram, 1024);
+
+        m.store(0x10, 0xab);
+        m.store(0x3ff, 0xcd);
+
+        m.mem.as_mut().unwrap().save().unwrap();
+
+        let mut saved = Vec::new();
+
+        File::open(&m.path).unwrap().read_to_end(&mut saved).unwrap();
+
+        assert_eq!(saved.len(), 1024);
+        assert_eq!(saved[0x10], 0xab);
+        assert_eq!(saved[0x3ff], 0xcd);
+
+        let mut tmp = m.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        assert!(fs::metadata(&tmp).is_err());
+    }
+}
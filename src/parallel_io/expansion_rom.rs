@@ -0,0 +1,121 @@
+//! Parallel I/O module mapping a ROM image in the EXPANSION 1
+//! region.
+//!
+//! At boot the BIOS looks for the string "Licensed by Sony Computer
+//! Entertainment Inc." at 0x1f000084 and, if it's found, jumps to
+//! 0x1f000080 before the kernel is fully initialized ("pre-boot"
+//! hook). Later on, after the kernel has been initialized, it checks
+//! for the same string at 0x1f000004 and jumps to 0x1f000000
+//! ("mid-boot" hook). This is how expansion cartridges (cheat
+//! devices, dev cartridges...) take over the boot sequence. Since the
+//! BIOS does all the work we just have to map the ROM image.
+
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, Read};
+
+use shared::SharedState;
+
+use super::ParallelIoModule;
+
+pub struct ExpansionRom {
+    /// ROM contents, mapped at the beginning of EXPANSION 1
+    rom: Vec<u8>,
+}
+
+impl ExpansionRom {
+    pub fn new(rom: Vec<u8>) -> Result<ExpansionRom, Error> {
+        if rom.len() > EXPANSION_1_SIZE {
+            return Err(Error::TooBig(rom.len()));
+        }
+
+        let rom = ExpansionRom {
+            rom: rom,
+        };
+
+        info!("Loaded expansion ROM: {}KB, pre-boot hook: {}, \
+               mid-boot hook: {}",
+              rom.rom.len() / 1024,
+              rom.has_preboot_hook(),
+              rom.has_midboot_hook());
+
+        if !rom.has_preboot_hook() && !rom.has_midboot_hook() {
+            warn!("Expansion ROM doesn't contain any license string, \
+                   the BIOS won't run it");
+        }
+
+        Ok(rom)
+    }
+
+    pub fn load_file(path: &Path) -> Result<ExpansionRom, Error> {
+        let f = try!(File::open(path));
+
+        let mut rom = Vec::new();
+
+        // Read one more byte than allowed to catch oversized images
+        try!(f.take(EXPANSION_1_SIZE as u64 + 1).read_to_end(&mut rom));
+
+        ExpansionRom::new(rom)
+    }
+
+    /// Returns true if the ROM contains the license string checked by
+    /// the BIOS right after reset
+    pub fn has_preboot_hook(&self) -> bool {
+        has_license_at(&self.rom, PREBOOT_LICENSE_OFFSET)
+    }
+
+    /// Returns true if the ROM contains the license string checked by
+    /// the BIOS after the kernel initialization
+    pub fn has_midboot_hook(&self) -> bool {
+        has_license_at(&self.rom, MIDBOOT_LICENSE_OFFSET)
+    }
+}
+
+impl ParallelIoModule for ExpansionRom {
+    fn load(&mut self, _: &mut SharedState, offset: u32) -> u8 {
+        // Past the end of the ROM the bus is floating
+        *self.rom.get(offset as usize).unwrap_or(&!0)
+    }
+
+    fn store(&mut self, _: &mut SharedState, offset: u32, val: u8) {
+        debug!("Write to expansion ROM: 0x{:x} 0x{:02x}", offset, val);
+    }
+}
+
+fn has_license_at(rom: &[u8], offset: usize) -> bool {
+    let end = offset + LICENSE_STRING.len();
+
+    match rom.get(offset..end) {
+        Some(s) => s == LICENSE_STRING,
+        None => false,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error while reading the ROM file
+    IoError(io::Error),
+    /// The ROM image doesn't fit in the EXPANSION 1 region
+    TooBig(usize),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::IoError(err)
+    }
+}
+
+/// License string looked up by the BIOS
+const LICENSE_STRING: &'static [u8] =
+    b"Licensed by Sony Computer Entertainment Inc.";
+
+/// Offset of the license string for the pre-boot hook, the entry
+/// point is at 0x80
+const PREBOOT_LICENSE_OFFSET: usize = 0x84;
+
+/// Offset of the license string for the mid-boot hook, the entry
+/// point is at 0x00
+const MIDBOOT_LICENSE_OFFSET: usize = 0x04;
+
+/// Size of the EXPANSION 1 memory region
+const EXPANSION_1_SIZE: usize = ::memory::map::EXPANSION_1.1 as usize;
@@ -1,12 +1,18 @@
 //! Emulation of the parallel interface (the Parallel I/O connector on
 //! the back of older PlayStation models)
 
+use std::io;
+
 use rustc_serialize::{Decodable, Encodable, Decoder, Encoder};
 
 use memory::Addressable;
 use shared::SharedState;
 
 pub mod exe_loader;
+pub mod cheat_cart;
+pub mod comms_link;
+pub mod expansion_rom;
+pub mod expansion_memory;
 
 pub struct ParallelIo {
     module: Box<ParallelIoModule>,
@@ -24,6 +30,13 @@ impl ParallelIo {
         self.module = module;
     }
 
+    /// Write any persistent state of the current module to disk.
+    /// Should be called periodically by the frontend so that the data
+    /// isn't lost if the emulator doesn't exit cleanly.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.module.flush()
+    }
+
     pub fn load<T: Addressable>(&mut self,
                                 shared: &mut SharedState,
                                 offset: u32) -> u32 {
@@ -37,6 +50,17 @@ impl ParallelIo {
 
         r
     }
+
+    pub fn store<T: Addressable>(&mut self,
+                                 shared: &mut SharedState,
+                                 offset: u32,
+                                 val: u32) {
+        for i in 0..T::size() {
+            let b = (val >> (8 * i)) as u8;
+
+            self.module.store(shared, offset + i as u32, b);
+        }
+    }
 }
 
 impl Encodable for ParallelIo {
@@ -67,6 +91,12 @@ pub trait ParallelIoModule {
     /// Parallel I/O byte store at offset `offset` (within the expansion 1
     /// memory region)
     fn store(&mut self, shared: &mut SharedState, offset: u32, val: u8);
+
+    /// Write any persistent state to disk. Most modules don't have
+    /// any.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// A dummy implementation of ParallelIo when nothing is connected
@@ -1,5 +1,7 @@
 use timekeeper::TimeKeeper;
-use interrupt::InterruptState;
+use interrupt::{Interrupt, InterruptState};
+use tracer::module_tracer;
+use event_log::{self, Event};
 
 /// State shared between various modules
 #[derive(RustcDecodable, RustcEncodable)]
@@ -30,6 +32,29 @@ impl SharedState {
         &mut self.irq_state
     }
 
+    /// Trigger the interrupt `which`, must be called on the rising
+    /// edge of the interrupt signal.
+    pub fn assert_irq(&mut self, which: Interrupt) {
+        self.irq_state.assert(which);
+
+        let status = self.irq_state.status();
+
+        event_log::log(self.tk.now(), || Event::Irq(status));
+
+        self.trace_irq();
+    }
+
+    /// Log the state of the interrupt controller
+    pub fn trace_irq(&mut self) {
+        let now = self.tk.now();
+        let irq_state = self.irq_state;
+
+        module_tracer("IRQ", |m| {
+            m.trace(now, "status", irq_state.status());
+            m.trace(now, "mask", irq_state.mask());
+        });
+    }
+
     pub fn counters(&self) -> &Counters {
         &self.counters
     }
@@ -0,0 +1,189 @@
+//! Backends for the SIO1 serial port, used to connect two emulator
+//! instances through a virtual link cable.
+//!
+//! The TCP link uses a very simple protocol: every message is two
+//! bytes long, the first one being the message type and the second
+//! one its payload:
+//!
+//! * `0x00 <byte>`: serial data byte
+//! * `0x01 <lines>`: new state of the sender's control outputs, bit 0
+//!   is DTR and bit 1 is RTS. They're wired to the receiver's DSR and
+//!   CTS inputs respectively.
+
+use std::io;
+use std::net::ToSocketAddrs;
+
+use tcp_port::TcpPort;
+
+/// Message exchanged between the two ends of the link
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A serial data byte
+    Data(u8),
+    /// Update of the sender's DTR and RTS output levels
+    Lines { dtr: bool, rts: bool },
+}
+
+impl Message {
+    fn encode(self) -> [u8; 2] {
+        match self {
+            Message::Data(b) => [0x00, b],
+            Message::Lines { dtr, rts } =>
+                [0x01, (dtr as u8) | ((rts as u8) << 1)],
+        }
+    }
+
+    fn decode(buf: [u8; 2]) -> Option<Message> {
+        match buf[0] {
+            0x00 => Some(Message::Data(buf[1])),
+            0x01 => Some(Message::Lines {
+                dtr: buf[1] & 1 != 0,
+                rts: buf[1] & 2 != 0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Since the serial port could be connected to different kinds of
+/// backends I abstract it behind a trait interface
+pub trait SerialLink {
+    /// Send `message` to the other end of the link
+    fn send(&mut self, message: Message);
+
+    /// Return the next message received from the other end of the
+    /// link or `None` if there's nothing pending. Must not block.
+    fn receive(&mut self) -> Option<Message>;
+
+    /// Return true if the other end of the link is connected
+    fn connected(&self) -> bool;
+
+    /// Return true if this is an actual backend which needs to be
+    /// polled even while it's not connected (waiting for an incoming
+    /// connection for instance)
+    fn is_plugged(&self) -> bool {
+        true
+    }
+}
+
+/// Dummy implementation of `SerialLink` when no cable is plugged in
+pub struct Disconnected;
+
+impl SerialLink for Disconnected {
+    fn send(&mut self, _: Message) {
+        // NOP
+    }
+
+    fn receive(&mut self) -> Option<Message> {
+        None
+    }
+
+    fn connected(&self) -> bool {
+        false
+    }
+
+    fn is_plugged(&self) -> bool {
+        false
+    }
+}
+
+/// Link cable bridged over a TCP connection
+pub struct TcpLink {
+    /// Connection to the other instance
+    port: TcpPort,
+    /// Buffer for partially received messages
+    rx_buf: [u8; 2],
+    /// Number of valid bytes in `rx_buf`
+    rx_len: usize,
+}
+
+impl TcpLink {
+    /// Wait for the other instance to connect on `addr`. This doesn't
+    /// block, the connection is accepted when the emulator polls the
+    /// link.
+    pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<TcpLink> {
+        let port = try!(TcpPort::listen(LOG_NAME, addr, false));
+
+        Ok(TcpLink::new(port))
+    }
+
+    /// Connect to an instance listening on `addr`
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpLink> {
+        let port = try!(TcpPort::connect(LOG_NAME, addr));
+
+        Ok(TcpLink::new(port))
+    }
+
+    fn new(port: TcpPort) -> TcpLink {
+        TcpLink {
+            port: port,
+            rx_buf: [0; 2],
+            rx_len: 0,
+        }
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn send(&mut self, message: Message) {
+        self.port.send(&message.encode());
+    }
+
+    fn receive(&mut self) -> Option<Message> {
+        loop {
+            let n = self.port.receive(&mut self.rx_buf[self.rx_len..]);
+
+            if n == 0 {
+                if !self.port.connected() {
+                    // Drop any partial message
+                    self.rx_len = 0;
+                }
+
+                return None;
+            }
+
+            self.rx_len += n;
+
+            if self.rx_len == self.rx_buf.len() {
+                self.rx_len = 0;
+
+                match Message::decode(self.rx_buf) {
+                    Some(m) => return Some(m),
+                    None => warn!("SIO1 link: invalid message {:?}",
+                                  self.rx_buf),
+                }
+            }
+        }
+    }
+
+    fn connected(&self) -> bool {
+        self.port.connected()
+    }
+}
+
+/// Name of the link in log messages
+const LOG_NAME: &'static str = "SIO1 link";
+
+#[test]
+fn message_encoding() {
+    let messages = [
+        Message::Data(0x00),
+        Message::Data(0xa5),
+        Message::Lines { dtr: false, rts: false },
+        Message::Lines { dtr: true, rts: false },
+        Message::Lines { dtr: false, rts: true },
+        Message::Lines { dtr: true, rts: true },
+    ];
+
+    for &m in &messages {
+        assert_eq!(Message::decode(m.encode()), Some(m));
+    }
+
+    assert_eq!(Message::Data(0x42).encode(), [0x00, 0x42]);
+    assert_eq!(Message::Lines { dtr: true, rts: false }.encode(),
+               [0x01, 0x01]);
+    assert_eq!(Message::Lines { dtr: false, rts: true }.encode(),
+               [0x01, 0x02]);
+
+    assert_eq!(Message::decode([0x02, 0x00]), None);
+    assert_eq!(Message::decode([0xff, 0x01]), None);
+}
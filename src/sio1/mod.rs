@@ -0,0 +1,593 @@
+//! Emulation of the second serial port (SIO1), used by link cable
+//! games to play head-to-head on two consoles.
+//!
+//! The serial link itself is abstracted behind the `SerialLink`
+//! trait, the `TcpLink` implementation can be used to bridge two
+//! emulator instances.
+//!
+//! The two instances are not synchronized: each one runs at its own
+//! pace and simply picks up the bytes received from the other when
+//! it polls the link. Games which expect an answer within a bounded
+//! number of cycles may time out if one instance lags behind (on a
+//! slow connection or when one emulator is paused) so link cable
+//! play isn't guaranteed to work. Doing better would require running
+//! both instances in lockstep.
+
+use rustc_serialize::{Decodable, Encodable, Decoder, Encoder};
+
+use memory::Addressable;
+use interrupt::Interrupt;
+use timekeeper::{Peripheral, Cycles};
+use shared::SharedState;
+
+use self::link::{SerialLink, Message, Disconnected};
+
+pub mod link;
+
+#[derive(RustcDecodable, RustcEncodable)]
+pub struct Sio1 {
+    /// Baudrate reload value
+    baud: u16,
+    /// Serial mode (baudrate factor, character length, parity, stop
+    /// bits). We don't really care about anything but the baudrate
+    /// factor since the link is emulated at the byte level.
+    mode: u8,
+    /// Control register, without the write-only bits
+    control: u16,
+    /// Misc register, R/W but unused
+    misc: u16,
+    /// Receive FIFO
+    rx_fifo: RxFifo,
+    /// Set when a byte is received while the RX FIFO is full
+    rx_overrun: bool,
+    /// Data Set Ready input, wired to the remote's DTR output
+    dsr: bool,
+    /// Clear To Send input, wired to the remote's RTS output
+    cts: bool,
+    /// Current interrupt level
+    interrupt: bool,
+    /// Serial link backend
+    link: Link,
+}
+
+impl Sio1 {
+    pub fn new() -> Sio1 {
+        Sio1 {
+            baud: 0,
+            mode: 0,
+            control: 0,
+            misc: 0,
+            rx_fifo: RxFifo::new(),
+            rx_overrun: false,
+            dsr: false,
+            cts: false,
+            interrupt: false,
+            link: Link::new(Box::new(Disconnected)),
+        }
+    }
+
+    /// Plug `link` into the serial port
+    pub fn set_link(&mut self,
+                    shared: &mut SharedState,
+                    link: Box<SerialLink>) {
+        self.link = Link::new(link);
+
+        // Start polling the new link, the remote will be told about
+        // our output levels as soon as it's connected
+        self.sync(shared);
+    }
+
+    pub fn load<T: Addressable>(&mut self,
+                                shared: &mut SharedState,
+                                offset: u32) -> u32 {
+        self.sync(shared);
+
+        match offset {
+            0 => {
+                let b = self.rx_fifo.pop();
+
+                self.maybe_interrupt(shared);
+
+                b as u32
+            }
+            4 => self.stat(),
+            8 => self.mode as u32,
+            10 => self.control as u32,
+            12 => self.misc as u32,
+            14 => self.baud as u32,
+            _ => panic!("Unhandled SIO1 read {:?} 0x{:x}",
+                        T::size(), offset),
+        }
+    }
+
+    pub fn store<T: Addressable>(&mut self,
+                                 shared: &mut SharedState,
+                                 offset: u32,
+                                 val: u32) {
+        self.sync(shared);
+
+        match offset {
+            0 => self.send(shared, val as u8),
+            8 => self.mode = val as u8,
+            10 => self.set_control(shared, val as u16),
+            12 => self.misc = val as u16,
+            14 => self.baud = val as u16,
+            _ => panic!("Unhandled write to SIO1 register {} {:04x}",
+                        offset, val as u16),
+        }
+    }
+
+    /// Poll the link for incoming messages
+    pub fn sync(&mut self, shared: &mut SharedState) {
+        shared.tk().sync(Peripheral::Sio1);
+
+        while let Some(m) = self.link.backend.receive() {
+            match m {
+                Message::Data(b) => {
+                    if !self.rx_enabled() {
+                        // Receiver disabled, drop the byte
+                        continue;
+                    }
+
+                    if !self.rx_fifo.push(b) {
+                        self.rx_overrun = true;
+                    }
+                }
+                Message::Lines { dtr, rts } => {
+                    self.dsr = dtr;
+                    self.cts = rts;
+                }
+            }
+        }
+
+        self.maybe_interrupt(shared);
+
+        let connected = self.link.backend.connected();
+
+        if connected && !self.link.connected {
+            // The remote just connected, let it know about our
+            // current output levels
+            self.send_lines();
+        }
+
+        self.link.connected = connected;
+
+        if connected {
+            // Poll again when the next byte could have been received
+            let period = self.byte_period();
+
+            shared.tk().set_next_sync_delta(Peripheral::Sio1, period);
+        } else if self.link.backend.is_plugged() {
+            // Keep polling in order to accept incoming connections
+            shared.tk().set_next_sync_delta(Peripheral::Sio1,
+                                            MIN_POLL_PERIOD);
+        } else {
+            shared.tk().no_sync_needed(Peripheral::Sio1);
+        }
+    }
+
+    fn send(&mut self, shared: &mut SharedState, b: u8) {
+        if !self.tx_enabled() {
+            warn!("SIO1 TX while transmitter is disabled: 0x{:02x}", b);
+            return;
+        }
+
+        // We transmit immediately so the TX FIFO is always ready
+        self.link.backend.send(Message::Data(b));
+
+        if self.control & CTRL_TX_IRQ != 0 {
+            self.assert_interrupt(shared);
+        }
+    }
+
+    fn set_control(&mut self, shared: &mut SharedState, ctrl: u16) {
+        if ctrl & 0x40 != 0 {
+            // Soft reset
+            self.mode = 0;
+            self.control = 0;
+            self.baud = 0;
+            self.rx_fifo = RxFifo::new();
+            self.rx_overrun = false;
+            self.interrupt = false;
+
+            self.send_lines();
+            return;
+        }
+
+        if ctrl & 0x10 != 0 {
+            // Interrupt and error flags acknowledge
+            self.interrupt = false;
+            self.rx_overrun = false;
+        }
+
+        let prev_lines = self.control & (CTRL_DTR | CTRL_RTS);
+
+        // Bits 4 and 6 are write-only
+        self.control = ctrl & !0x50;
+
+        if self.control & (CTRL_DTR | CTRL_RTS) != prev_lines {
+            self.send_lines();
+        }
+
+        self.maybe_interrupt(shared);
+    }
+
+    /// Send the current level of our DTR and RTS outputs to the
+    /// remote
+    fn send_lines(&mut self) {
+        let lines = Message::Lines {
+            dtr: self.control & CTRL_DTR != 0,
+            rts: self.control & CTRL_RTS != 0,
+        };
+
+        self.link.backend.send(lines);
+    }
+
+    /// Assert the interrupt if one of the enabled conditions is met
+    fn maybe_interrupt(&mut self, shared: &mut SharedState) {
+        let rx_threshold = 1 << ((self.control >> 8) & 3);
+
+        let rx_irq = self.control & CTRL_RX_IRQ != 0 &&
+            self.rx_fifo.len() >= rx_threshold;
+
+        let dsr_irq = self.control & CTRL_DSR_IRQ != 0 && self.dsr;
+
+        if rx_irq || dsr_irq {
+            self.assert_interrupt(shared);
+        }
+    }
+
+    fn assert_interrupt(&mut self, shared: &mut SharedState) {
+        if !self.interrupt {
+            // Rising edge of the interrupt
+            shared.assert_irq(Interrupt::Sio);
+        }
+
+        self.interrupt = true;
+    }
+
+    fn tx_enabled(&self) -> bool {
+        self.control & CTRL_TX_EN != 0
+    }
+
+    fn rx_enabled(&self) -> bool {
+        self.control & CTRL_RX_EN != 0
+    }
+
+    /// Duration of a single byte transfer (start bit, 8 data bits and
+    /// stop bit) in CPU cycles
+    fn byte_period(&self) -> Cycles {
+        let factor =
+            match self.mode & 3 {
+                2 => 16,
+                3 => 64,
+                _ => 1,
+            };
+
+        let period = self.baud as Cycles * factor * 10;
+
+        // Make sure we don't end up polling constantly if the
+        // baudrate hasn't been configured
+        if period < MIN_POLL_PERIOD {
+            MIN_POLL_PERIOD
+        } else {
+            period
+        }
+    }
+
+    fn stat(&self) -> u32 {
+        let mut stat = 0u32;
+
+        // TX Ready bits 0 and 2, we transmit immediately
+        stat |= 5;
+        stat |= (!self.rx_fifo.is_empty() as u32) << 1;
+        stat |= (self.rx_overrun as u32) << 4;
+        stat |= (self.dsr as u32) << 7;
+        stat |= (self.cts as u32) << 8;
+        stat |= (self.interrupt as u32) << 9;
+
+        stat
+    }
+}
+
+/// The SIO1 RX FIFO can hold up to 8 bytes
+#[derive(RustcDecodable, RustcEncodable)]
+struct RxFifo {
+    buffer: [u8; 8],
+    /// Index of the oldest byte in `buffer`
+    read_index: usize,
+    /// Number of bytes in the FIFO
+    len: usize,
+}
+
+impl RxFifo {
+    fn new() -> RxFifo {
+        RxFifo {
+            buffer: [0; 8],
+            read_index: 0,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Push `b` in the FIFO, returns `false` if the FIFO is full
+    fn push(&mut self, b: u8) -> bool {
+        if self.len == self.buffer.len() {
+            return false;
+        }
+
+        let index = (self.read_index + self.len) % self.buffer.len();
+
+        self.buffer[index] = b;
+        self.len += 1;
+
+        true
+    }
+
+    fn pop(&mut self) -> u8 {
+        if self.is_empty() {
+            // XXX Not sure what the real hardware returns here
+            return 0;
+        }
+
+        let b = self.buffer[self.read_index];
+
+        self.read_index = (self.read_index + 1) % self.buffer.len();
+        self.len -= 1;
+
+        b
+    }
+}
+
+/// Wrapper around the link backend to skip it in savestates
+struct Link {
+    backend: Box<SerialLink>,
+    /// State of the connection the last time the link was polled,
+    /// used to detect new connections
+    connected: bool,
+}
+
+impl Link {
+    fn new(backend: Box<SerialLink>) -> Link {
+        Link {
+            backend: backend,
+            connected: false,
+        }
+    }
+}
+
+impl Encodable for Link {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        // We can't serialize a network connection, the frontend will
+        // have to reconnect the link after loading the savestate.
+        s.emit_nil()
+    }
+}
+
+impl Decodable for Link {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Link, D::Error> {
+        try!(d.read_nil());
+
+        Ok(Link::new(Box::new(Disconnected)))
+    }
+}
+
+/// Control register: transmitter enable
+const CTRL_TX_EN: u16 = 1 << 0;
+/// Control register: DTR output level
+const CTRL_DTR: u16 = 1 << 1;
+/// Control register: receiver enable
+const CTRL_RX_EN: u16 = 1 << 2;
+/// Control register: RTS output level
+const CTRL_RTS: u16 = 1 << 5;
+/// Control register: TX interrupt enable
+const CTRL_TX_IRQ: u16 = 1 << 10;
+/// Control register: RX interrupt enable
+const CTRL_RX_IRQ: u16 = 1 << 11;
+/// Control register: DSR interrupt enable
+const CTRL_DSR_IRQ: u16 = 1 << 12;
+
+/// Minimum delay between two link polls in CPU cycles
+const MIN_POLL_PERIOD: Cycles = 1000;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use memory::HalfWord;
+    use shared::SharedState;
+
+    use super::{Sio1, RxFifo};
+    use super::{CTRL_TX_EN, CTRL_RX_EN, CTRL_DTR, CTRL_RTS, CTRL_RX_IRQ};
+    use super::link::{SerialLink, Message};
+
+    /// Both ends of a fake link, shared between the test and the
+    /// `SerialLink` plugged into the SIO1
+    struct Wire {
+        connected: bool,
+        /// Messages waiting to be received by the SIO1
+        rx: VecDeque<Message>,
+        /// Messages sent by the SIO1
+        tx: Vec<Message>,
+    }
+
+    struct TestLink(Rc<RefCell<Wire>>);
+
+    impl SerialLink for TestLink {
+        fn send(&mut self, message: Message) {
+            self.0.borrow_mut().tx.push(message);
+        }
+
+        fn receive(&mut self) -> Option<Message> {
+            self.0.borrow_mut().rx.pop_front()
+        }
+
+        fn connected(&self) -> bool {
+            self.0.borrow().connected
+        }
+    }
+
+    fn plug(sio1: &mut Sio1,
+            shared: &mut SharedState,
+            connected: bool) -> Rc<RefCell<Wire>> {
+        let wire = Rc::new(RefCell::new(Wire {
+            connected: connected,
+            rx: VecDeque::new(),
+            tx: Vec::new(),
+        }));
+
+        sio1.set_link(shared, Box::new(TestLink(wire.clone())));
+
+        wire
+    }
+
+    fn stat(sio1: &mut Sio1, shared: &mut SharedState) -> u32 {
+        sio1.load::<HalfWord>(shared, 4)
+    }
+
+    fn set_control(sio1: &mut Sio1, shared: &mut SharedState, ctrl: u16) {
+        sio1.store::<HalfWord>(shared, 10, ctrl as u32);
+    }
+
+    #[test]
+    fn rx_fifo() {
+        let mut fifo = RxFifo::new();
+
+        assert!(fifo.is_empty());
+        assert_eq!(fifo.pop(), 0);
+
+        // Fill it, then move the read index around to check the
+        // wrap around
+        for round in 0..3 {
+            for i in 0..8 {
+                assert!(fifo.push(round * 8 + i));
+            }
+
+            assert_eq!(fifo.len(), 8);
+            assert!(!fifo.push(0xff));
+
+            for i in 0..5 {
+                assert_eq!(fifo.pop(), round * 8 + i);
+            }
+
+            for i in 5..8 {
+                assert_eq!(fifo.pop(), round * 8 + i);
+            }
+
+            assert!(fifo.is_empty());
+
+            fifo.push(0xaa);
+            assert_eq!(fifo.pop(), 0xaa);
+        }
+    }
+
+    #[test]
+    fn lines_sent_on_connection() {
+        let mut shared = SharedState::new();
+        let mut sio1 = Sio1::new();
+
+        let wire = plug(&mut sio1, &mut shared, false);
+
+        set_control(&mut sio1, &mut shared, CTRL_DTR);
+
+        wire.borrow_mut().tx.clear();
+
+        // Nothing new while the remote isn't there
+        sio1.sync(&mut shared);
+        assert!(wire.borrow().tx.is_empty());
+
+        wire.borrow_mut().connected = true;
+
+        sio1.sync(&mut shared);
+        assert_eq!(wire.borrow().tx,
+                   vec![Message::Lines { dtr: true, rts: false }]);
+
+        // Only once per connection
+        sio1.sync(&mut shared);
+        assert_eq!(wire.borrow().tx.len(), 1);
+
+        // Line changes are forwarded immediately
+        set_control(&mut sio1, &mut shared, CTRL_DTR | CTRL_RTS);
+        assert_eq!(wire.borrow().tx[1],
+                   Message::Lines { dtr: true, rts: true });
+    }
+
+    #[test]
+    fn stat_and_control() {
+        let mut shared = SharedState::new();
+        let mut sio1 = Sio1::new();
+
+        let wire = plug(&mut sio1, &mut shared, true);
+
+        // TX always ready, nothing received
+        assert_eq!(stat(&mut sio1, &mut shared), 0x5);
+
+        // Transmitter disabled: the byte is dropped
+        sio1.store::<HalfWord>(&mut shared, 0, 0x12);
+        assert!(!wire.borrow().tx.contains(&Message::Data(0x12)));
+
+        set_control(&mut sio1, &mut shared, CTRL_TX_EN | CTRL_RX_EN);
+
+        sio1.store::<HalfWord>(&mut shared, 0, 0x34);
+        assert_eq!(wire.borrow().tx.last(), Some(&Message::Data(0x34)));
+
+        wire.borrow_mut().rx.push_back(Message::Data(0x56));
+        wire.borrow_mut().rx.push_back(Message::Lines { dtr: true,
+                                                        rts: true });
+
+        // RX not empty, DSR and CTS
+        assert_eq!(stat(&mut sio1, &mut shared), 0x5 | 0x2 | 0x180);
+
+        assert_eq!(sio1.load::<HalfWord>(&mut shared, 0), 0x56);
+        assert_eq!(stat(&mut sio1, &mut shared), 0x5 | 0x180);
+
+        // Overrun
+        for i in 0..9 {
+            wire.borrow_mut().rx.push_back(Message::Data(i));
+        }
+
+        assert_eq!(stat(&mut sio1, &mut shared) & 0x12, 0x12);
+
+        // The acknowledge bit clears the error flag and isn't
+        // stored in the control register
+        set_control(&mut sio1, &mut shared, CTRL_TX_EN | CTRL_RX_EN | 0x10);
+        assert_eq!(stat(&mut sio1, &mut shared) & 0x10, 0);
+        assert_eq!(sio1.load::<HalfWord>(&mut shared, 10),
+                   (CTRL_TX_EN | CTRL_RX_EN) as u32);
+
+        // Soft reset
+        set_control(&mut sio1, &mut shared, 0x40);
+        assert_eq!(sio1.load::<HalfWord>(&mut shared, 10), 0);
+        assert_eq!(stat(&mut sio1, &mut shared), 0x5 | 0x180);
+    }
+
+    #[test]
+    fn rx_interrupt() {
+        let mut shared = SharedState::new();
+        let mut sio1 = Sio1::new();
+
+        let wire = plug(&mut sio1, &mut shared, true);
+
+        // RX interrupt after 2 bytes
+        set_control(&mut sio1, &mut shared,
+                    CTRL_RX_EN | CTRL_RX_IRQ | (1 << 8));
+
+        wire.borrow_mut().rx.push_back(Message::Data(1));
+        assert_eq!(stat(&mut sio1, &mut shared) & 0x200, 0);
+
+        wire.borrow_mut().rx.push_back(Message::Data(2));
+        assert_eq!(stat(&mut sio1, &mut shared) & 0x200, 0x200);
+
+        set_control(&mut sio1, &mut shared, CTRL_RX_EN | 0x10);
+        assert_eq!(stat(&mut sio1, &mut shared) & 0x200, 0);
+    }
+}
@@ -0,0 +1,194 @@
+//! Non-blocking TCP connection used by the various link backends
+//! (SIO1 link cable, cheat cartridge comms link...). The emulator
+//! polls the port regularly, nothing here ever blocks: data that
+//! can't be sent right away is buffered and flushed on the next poll.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+pub struct TcpPort {
+    /// Name of the port, used in log messages
+    name: &'static str,
+    /// Listening socket if we accept incoming connections
+    listener: Option<TcpListener>,
+    /// If true we keep listening for new connections after the first
+    /// one has been accepted, otherwise the listener is closed
+    keep_listening: bool,
+    /// Current connection
+    stream: Option<TcpStream>,
+    /// Data waiting to be sent when the socket's send buffer is full
+    tx_buf: Vec<u8>,
+}
+
+impl TcpPort {
+    /// Listen for connections on `addr`. The connection is accepted
+    /// when the port is polled. If `keep_listening` is true a new
+    /// connection can be accepted after a disconnection.
+    pub fn listen<A: ToSocketAddrs>(name: &'static str,
+                                    addr: A,
+                                    keep_listening: bool)
+                                    -> io::Result<TcpPort> {
+        let listener = try!(TcpListener::bind(addr));
+
+        try!(listener.set_nonblocking(true));
+
+        info!("{}: listening on {:?}", name, listener.local_addr());
+
+        Ok(TcpPort {
+            name: name,
+            listener: Some(listener),
+            keep_listening: keep_listening,
+            stream: None,
+            tx_buf: Vec::new(),
+        })
+    }
+
+    /// Connect to `addr`
+    pub fn connect<A: ToSocketAddrs>(name: &'static str,
+                                     addr: A) -> io::Result<TcpPort> {
+        let stream = try!(TcpStream::connect(addr));
+
+        try!(setup_stream(&stream));
+
+        info!("{}: connected to {:?}", name, stream.peer_addr());
+
+        Ok(TcpPort {
+            name: name,
+            listener: None,
+            keep_listening: false,
+            stream: Some(stream),
+            tx_buf: Vec::new(),
+        })
+    }
+
+    pub fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Send `buf` to the remote. The data is dropped if we're not
+    /// connected. If the remote doesn't keep up the data is queued
+    /// and sent in order when the port is polled.
+    pub fn send(&mut self, buf: &[u8]) {
+        if self.stream.is_none() {
+            // Nobody's listening
+            return;
+        }
+
+        self.tx_buf.extend_from_slice(buf);
+
+        self.flush();
+    }
+
+    /// Accept incoming connections and send any queued data
+    pub fn poll(&mut self) {
+        self.poll_listener();
+        self.flush();
+    }
+
+    /// Read whatever is available into `buf` and return the number
+    /// of bytes received. Returns 0 if nothing is pending or if we're
+    /// not connected.
+    pub fn receive(&mut self, buf: &mut [u8]) -> usize {
+        self.poll();
+
+        loop {
+            let res =
+                match self.stream {
+                    Some(ref mut s) => s.read(buf),
+                    None => return 0,
+                };
+
+            match res {
+                Ok(0) => {
+                    let eof = io::Error::new(io::ErrorKind::UnexpectedEof,
+                                             "remote closed the connection");
+                    self.disconnect(eof);
+                    return 0;
+                }
+                Ok(n) => return n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock =>
+                    return 0,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => {
+                    self.disconnect(e);
+                    return 0;
+                }
+            }
+        }
+    }
+
+    /// Accept an incoming connection if we're not connected
+    fn poll_listener(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+
+        let accepted =
+            match self.listener {
+                Some(ref l) => l.accept(),
+                None => return,
+            };
+
+        match accepted {
+            Ok((stream, addr)) => {
+                if let Err(e) = setup_stream(&stream) {
+                    warn!("{}: can't configure connection: {}", self.name, e);
+                    return;
+                }
+
+                info!("{}: connection from {}", self.name, addr);
+
+                self.stream = Some(stream);
+
+                if !self.keep_listening {
+                    self.listener = None;
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(e) => warn!("{}: accept failed: {}", self.name, e),
+        }
+    }
+
+    /// Write as much of `tx_buf` as the socket accepts without
+    /// blocking
+    fn flush(&mut self) {
+        while !self.tx_buf.is_empty() {
+            let res =
+                match self.stream {
+                    Some(ref mut s) => s.write(&self.tx_buf),
+                    None => return,
+                };
+
+            match res {
+                Ok(0) => {
+                    let eof = io::Error::new(io::ErrorKind::WriteZero,
+                                             "remote closed the connection");
+                    self.disconnect(eof);
+                }
+                Ok(n) => {
+                    self.tx_buf.drain(..n);
+                }
+                // The remote is lagging behind, try again later
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock =>
+                    return,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => self.disconnect(e),
+            }
+        }
+    }
+
+    fn disconnect(&mut self, reason: io::Error) {
+        warn!("{}: connection lost: {}", self.name, reason);
+
+        self.stream = None;
+        self.tx_buf.clear();
+    }
+}
+
+fn setup_stream(stream: &TcpStream) -> io::Result<()> {
+    // The links exchange tiny, latency-sensitive messages
+    try!(stream.set_nodelay(true));
+
+    stream.set_nonblocking(true)
+}
@@ -19,6 +19,8 @@ pub enum Peripheral {
     PadMemCard,
     /// CD-ROM controller
     CdRom,
+    /// Second serial port
+    Sio1,
 }
 
 
@@ -31,7 +33,7 @@ pub struct TimeKeeper {
     /// Next time a peripheral needs an update
     next_sync: Cycles,
     /// Time sheets for keeping track of the various peripherals
-    timesheets: [TimeSheet; 6],
+    timesheets: [TimeSheet; 7],
 }
 
 impl TimeKeeper {
@@ -40,7 +42,7 @@ impl TimeKeeper {
             now: 0,
             // Force a sync at the start to initialize evrything
             next_sync: 0,
-            timesheets: [TimeSheet::new(); 6],
+            timesheets: [TimeSheet::new(); 7],
         }
     }
 
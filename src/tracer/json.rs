@@ -0,0 +1,55 @@
+//! Dump traces as JSON lines: one JSON object per value change or
+//! event, sorted by date. It's easy to process with scripts in order to diff
+//! the behaviour of the emulator against other emulators.
+//!
+//! Each line looks like:
+//!
+//! ```text
+//! {"date":1234,"module":"GPU","variable":"gp0","size":32,"value":3758096384}
+//! ```
+//!
+//! `date` is expressed in CPU cycles since the start of the
+//! emulation.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use rustc_serialize::json;
+
+use super::{Module, ValueSize, ValueType, sorted_events};
+
+#[derive(RustcEncodable)]
+struct JsonEvent {
+    date: u64,
+    module: &'static str,
+    variable: &'static str,
+    size: ValueSize,
+    value: ValueType,
+}
+
+/// Write `trace` to `w` in JSON lines format
+pub fn write(trace: &HashMap<&'static str, Module>,
+             w: &mut Write) -> io::Result<()> {
+    let (events, _) = sorted_events(trace);
+
+    for e in events {
+        let event = JsonEvent {
+            date: e.date,
+            module: e.module,
+            variable: e.variable,
+            size: e.size,
+            value: e.value,
+        };
+
+        let line =
+            match json::encode(&event) {
+                Ok(l) => l,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other,
+                                                    format!("{:?}", e))),
+            };
+
+        try!(writeln!(w, "{}", line));
+    }
+
+    Ok(())
+}
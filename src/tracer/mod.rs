@@ -0,0 +1,457 @@
+//! Interface used to log internal variables in order to generate
+//! traces
+//!
+//! Tracing is only active when the crate is built with the `trace`
+//! feature. Each module can be enabled or disabled at runtime and the
+//! resulting trace can be dumped as a VCD (see the `vcd` module) or
+//! as JSON lines (see the `json` module).
+
+use std::collections::HashMap;
+
+pub mod vcd;
+pub mod json;
+
+pub type ValueType  = u32;
+pub type ValueSize  = u8;
+
+/// Underlying type of every logged value. Since we use a `u32` we
+/// only support variables up to 32bits for now. The 2nd parameter is
+/// the size of the value in bits.
+#[derive(Copy, Clone)]
+pub struct SizedValue(pub ValueType, pub ValueSize);
+
+impl From<bool> for SizedValue {
+    fn from(v: bool) -> SizedValue {
+        SizedValue(v as ValueType, 1)
+    }
+}
+
+impl From<u8> for SizedValue {
+    fn from(v: u8) -> SizedValue {
+        SizedValue(v as ValueType, 8)
+    }
+}
+
+impl From<u16> for SizedValue {
+    fn from(v: u16) -> SizedValue {
+        SizedValue(v as ValueType, 16)
+    }
+}
+
+impl From<u32> for SizedValue {
+    fn from(v: u32) -> SizedValue {
+        SizedValue(v as ValueType, 32)
+    }
+}
+
+pub struct Variable {
+    size: ValueSize,
+    /// True if the variable logs events (see `Module::event`) instead
+    /// of value changes
+    event: bool,
+    /// Log for this variable: `(date, value)`
+    log: Vec<(u64, ValueType)>,
+}
+
+impl Variable {
+    fn new(size: ValueSize, event: bool) -> Variable {
+        Variable {
+            size: size,
+            event: event,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> ValueSize {
+        self.size
+    }
+
+    pub fn is_event(&self) -> bool {
+        self.event
+    }
+
+    pub fn log(&self) -> &Vec<(u64, ValueType)> {
+        &self.log
+    }
+}
+
+pub struct Module {
+    /// Variables, indexed by name.
+    variables: HashMap<&'static str, Variable>,
+}
+
+impl Module {
+    #[cfg(feature = "trace")]
+    fn new() -> Module {
+        Module {
+            variables: HashMap::new(),
+        }
+    }
+
+    pub fn variables(&self) -> &HashMap<&'static str, Variable> {
+        &self.variables
+    }
+
+    /// Log the value of a state variable. Only changes of value are
+    /// recorded, if the variable changes several times during the
+    /// same cycle only the last value is kept.
+    pub fn trace<V: Into<SizedValue>>(&mut self,
+                                      date: u64,
+                                      name: &'static str,
+                                      sized_value: V) {
+        let SizedValue(value, size) = sized_value.into();
+
+        let var = self.variable_mut(name, size, false);
+
+        if let Some(last) = var.log.last_mut() {
+            let (last_date, last_value) = *last;
+
+            if last_date > date {
+                panic!("Got out-of-order events for {} ({} > {})",
+                       name, last_date, date);
+            }
+
+            if last_value == value {
+                // No value change
+                return;
+            }
+
+            if last_date == date {
+                // Several changes during the same cycle, only keep
+                // the last one
+                last.1 = value;
+            }
+        }
+
+        let len = var.log.len();
+
+        if len > 0 && var.log[len - 1].0 == date {
+            // If we went back to the previous value during this
+            // cycle there's no change left to record
+            if len > 1 && var.log[len - 2].1 == value {
+                var.log.pop();
+            }
+
+            return;
+        }
+
+        var.log.push((date, value));
+    }
+
+    /// Log a one-off event such as a command or register write. Unlike
+    /// `trace` every call is recorded, even if the value is the same
+    /// as the previous one or if several events occur during the same
+    /// cycle.
+    pub fn event<V: Into<SizedValue>>(&mut self,
+                                      date: u64,
+                                      name: &'static str,
+                                      sized_value: V) {
+        let SizedValue(value, size) = sized_value.into();
+
+        let var = self.variable_mut(name, size, true);
+
+        if let Some(&(last_date, _)) = var.log.last() {
+            if last_date > date {
+                panic!("Got out-of-order events for {} ({} > {})",
+                       name, last_date, date);
+            }
+        }
+
+        var.log.push((date, value));
+    }
+
+    fn variable_mut(&mut self,
+                    name: &'static str,
+                    size: ValueSize,
+                    event: bool) -> &mut Variable {
+        let var =
+            self.variables.entry(name).or_insert(Variable::new(size, event));
+
+        if var.size != size {
+            panic!("Incoherent size for variable {}: got {} and {}",
+                   name, var.size, size);
+        }
+
+        if var.event != event {
+            panic!("Variable {} used both as an event and a value", name);
+        }
+
+        var
+    }
+}
+
+/// A single value change or event, used when dumping traces
+struct Event {
+    date: u64,
+    module: &'static str,
+    variable: &'static str,
+    size: ValueSize,
+    value: ValueType,
+    /// Index of the variable in the trace, unique across all modules
+    index: usize,
+}
+
+/// Description of a variable in a trace, used when dumping traces
+struct TracedVariable {
+    module: &'static str,
+    name: &'static str,
+    size: ValueSize,
+    /// True if the variable logs events, see `Variable::is_event`
+    event: bool,
+}
+
+/// Flatten `trace` into a list of events sorted by date. Events are
+/// stable-sorted so changes occurring at the same date keep the
+/// module/variable ordering and events of a single variable stay in
+/// the order they were logged. Also returns the list of variables,
+/// indexed by `Event::index`.
+fn sorted_events(trace: &HashMap<&'static str, Module>)
+                 -> (Vec<Event>, Vec<TracedVariable>) {
+    let mut modules: Vec<_> = trace.iter().collect();
+
+    // Sort by name in order to get a reproducible output
+    modules.sort_by_key(|&(name, _)| *name);
+
+    let mut variables = Vec::new();
+    let mut events = Vec::new();
+
+    for (&module_name, module) in modules {
+        let mut vars: Vec<_> = module.variables().iter().collect();
+
+        vars.sort_by_key(|&(name, _)| *name);
+
+        for (&var_name, var) in vars {
+            let index = variables.len();
+
+            variables.push(TracedVariable {
+                module: module_name,
+                name: var_name,
+                size: var.size(),
+                event: var.is_event(),
+            });
+
+            for &(date, value) in var.log() {
+                events.push(Event {
+                    date: date,
+                    module: module_name,
+                    variable: var_name,
+                    size: var.size(),
+                    value: value,
+                    index: index,
+                });
+            }
+        }
+    }
+
+    events.sort_by_key(|e| e.date);
+
+    (events, variables)
+}
+
+#[cfg(feature = "trace")]
+pub struct Tracer {
+    /// Modules, indexed by name
+    modules: HashMap<&'static str, Module>,
+    /// Modules explicitly enabled or disabled with
+    /// `set_module_enabled`
+    enabled: HashMap<String, bool>,
+    /// Whether modules not present in `enabled` are traced
+    default_enabled: bool,
+}
+
+#[cfg(feature = "trace")]
+impl Tracer {
+    fn new() -> Tracer {
+        Tracer {
+            modules: HashMap::new(),
+            enabled: HashMap::new(),
+            default_enabled: true,
+        }
+    }
+
+    fn module_enabled(&self, name: &str) -> bool {
+        *self.enabled.get(name).unwrap_or(&self.default_enabled)
+    }
+
+    fn module_mut(&mut self, name: &'static str) -> &mut Module {
+        self.modules.entry(name).or_insert(Module::new())
+    }
+
+    /// Reset the tracer and return the content of the previous trace
+    fn remove_trace(&mut self) -> HashMap<&'static str, Module> {
+        let mut swap = HashMap::new();
+
+        ::std::mem::swap(&mut self.modules, &mut swap);
+
+        swap
+    }
+}
+
+/// Global logger instance
+#[cfg(feature = "trace")]
+lazy_static! {
+    static ref LOGGER: ::std::sync::Mutex<Tracer> = {
+        ::std::sync::Mutex::new(Tracer::new())
+    };
+}
+
+#[cfg(feature = "trace")]
+pub fn remove_trace() -> HashMap<&'static str, Module> {
+    let mut logger = LOGGER.lock().unwrap();
+
+    logger.remove_trace()
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn remove_trace() -> HashMap<&'static str, Module> {
+    HashMap::new()
+}
+
+/// Enable or disable tracing for module `name` ("GPU", "CDROM",
+/// "DMA"...)
+#[cfg(feature = "trace")]
+pub fn set_module_enabled(name: &str, enabled: bool) {
+    let mut logger = LOGGER.lock().unwrap();
+
+    logger.enabled.insert(name.into(), enabled);
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn set_module_enabled(_name: &str, _enabled: bool) {
+    // NOP
+}
+
+/// Set whether the modules which haven't been configured with
+/// `set_module_enabled` are traced. Defaults to `true`.
+#[cfg(feature = "trace")]
+pub fn set_default_enabled(enabled: bool) {
+    let mut logger = LOGGER.lock().unwrap();
+
+    logger.default_enabled = enabled;
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn set_default_enabled(_enabled: bool) {
+    // NOP
+}
+
+#[cfg(feature = "trace")]
+pub fn module_tracer<F>(name: &'static str, f: F)
+    where F: FnOnce(&mut Module) {
+
+    let mut logger = LOGGER.lock().unwrap();
+
+    if !logger.module_enabled(name) {
+        return;
+    }
+
+    let module = logger.module_mut(name);
+
+    f(module);
+}
+
+#[cfg(not(feature = "trace"))]
+#[inline(always)]
+pub fn module_tracer<F>(_name: &'static str, _f: F)
+    where F: FnOnce(&mut Module) {
+    // NOP
+}
+
+#[cfg(test)]
+fn test_module() -> Module {
+    Module {
+        variables: HashMap::new(),
+    }
+}
+
+#[test]
+fn trace_same_cycle() {
+    let mut m = test_module();
+
+    let log = |m: &Module| m.variables()["v"].log().clone();
+
+    m.trace(5, "v", 1u8);
+    m.trace(5, "v", 2u8);
+    assert_eq!(log(&m), vec![(5, 2)]);
+
+    // No change
+    m.trace(7, "v", 2u8);
+    assert_eq!(log(&m), vec![(5, 2)]);
+
+    m.trace(10, "v", 3u8);
+    m.trace(10, "v", 4u8);
+    assert_eq!(log(&m), vec![(5, 2), (10, 4)]);
+
+    // Back to the previous value within the same cycle
+    m.trace(10, "v", 2u8);
+    assert_eq!(log(&m), vec![(5, 2)]);
+
+    m.trace(12, "v", 5u8);
+    assert_eq!(log(&m), vec![(5, 2), (12, 5)]);
+}
+
+#[test]
+fn event_keeps_everything() {
+    let mut m = test_module();
+
+    m.event(5, "e", 1u32);
+    m.event(5, "e", 1u32);
+    m.event(5, "e", 2u32);
+    m.event(6, "e", 2u32);
+
+    let var = &m.variables()["e"];
+
+    assert!(var.is_event());
+    assert_eq!(var.size(), 32);
+    assert_eq!(*var.log(), vec![(5, 1), (5, 1), (5, 2), (6, 2)]);
+}
+
+#[test]
+fn sorted_events_order() {
+    let mut a = test_module();
+    let mut b = test_module();
+
+    b.trace(1, "x", 1u8);
+    b.event(3, "cmd", 0x10u8);
+
+    a.trace(3, "z", true);
+    a.trace(3, "y", 0x1234u16);
+    a.event(2, "w", 7u32);
+    a.event(3, "w", 8u32);
+    a.event(3, "w", 9u32);
+
+    let mut trace = HashMap::new();
+
+    trace.insert("B", b);
+    trace.insert("A", a);
+
+    let (events, variables) = sorted_events(&trace);
+
+    let names: Vec<_> =
+        variables.iter().map(|v| (v.module, v.name, v.event)).collect();
+
+    assert_eq!(names, vec![("A", "w", true),
+                           ("A", "y", false),
+                           ("A", "z", false),
+                           ("B", "cmd", true),
+                           ("B", "x", false)]);
+
+    let dumped: Vec<_> =
+        events.iter()
+        .map(|e| (e.date, e.module, e.variable, e.value))
+        .collect();
+
+    assert_eq!(dumped, vec![(1, "B", "x", 1),
+                            (2, "A", "w", 7),
+                            (3, "A", "w", 8),
+                            (3, "A", "w", 9),
+                            (3, "A", "y", 0x1234),
+                            (3, "A", "z", 1),
+                            (3, "B", "cmd", 0x10)]);
+
+    for e in &events {
+        let var = &variables[e.index];
+
+        assert_eq!((var.module, var.name), (e.module, e.variable));
+    }
+}
@@ -0,0 +1,169 @@
+//! Dump traces in the Value Change Dump format, which can be opened
+//! with waveform viewers like GTKWave and compared against logic
+//! analyzer captures.
+//!
+//! Event variables (see `Module::event`) are dumped as a VCD `event`
+//! named after the variable, triggered every time the event is
+//! logged, and a `<name>_value` wire holding the event's value. That
+//! way repeated events with the same value are visible. VCD can't
+//! represent several values at the same timestamp however: if a
+//! variable changes or an event fires several times during the same
+//! CPU cycle (or within the same nanosecond) viewers only display
+//! the last value. Use the JSON output (see the `json` module) to get
+//! every single event.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::{Module, sorted_events};
+
+/// Write `trace` to `w` in VCD format. Each tracer module becomes a
+/// VCD scope. Dates are converted from CPU cycles to nanoseconds.
+pub fn write(trace: &HashMap<&'static str, Module>,
+             w: &mut Write) -> io::Result<()> {
+    let (events, variables) = sorted_events(trace);
+
+    try!(writeln!(w, "$version rustation {} $end", ::VERSION));
+    try!(writeln!(w, "$timescale 1 ns $end"));
+
+    let mut cur_module = None;
+
+    // VCD identifiers for each variable: `(trigger, value)`. The
+    // trigger is only used for event variables.
+    let mut ids = Vec::with_capacity(variables.len());
+    let mut next_id = 0;
+
+    for var in &variables {
+        if cur_module != Some(var.module) {
+            if cur_module.is_some() {
+                try!(writeln!(w, "$upscope $end"));
+            }
+
+            try!(writeln!(w, "$scope module {} $end", var.module));
+
+            cur_module = Some(var.module);
+        }
+
+        if var.event {
+            let trigger = identifier(next_id);
+            let value = identifier(next_id + 1);
+
+            next_id += 2;
+
+            try!(writeln!(w, "$var event 1 {} {} $end", trigger, var.name));
+            try!(writeln!(w, "$var wire {} {} {}_value $end",
+                          var.size, value, var.name));
+
+            ids.push((Some(trigger), value));
+        } else {
+            let value = identifier(next_id);
+
+            next_id += 1;
+
+            try!(writeln!(w, "$var wire {} {} {} $end",
+                          var.size, value, var.name));
+
+            ids.push((None, value));
+        }
+    }
+
+    if cur_module.is_some() {
+        try!(writeln!(w, "$upscope $end"));
+    }
+
+    try!(writeln!(w, "$enddefinitions $end"));
+
+    let mut cur_time = None;
+
+    for e in events {
+        let time = cycles_to_ns(e.date);
+
+        if cur_time != Some(time) {
+            try!(writeln!(w, "#{}", time));
+            cur_time = Some(time);
+        }
+
+        let (ref trigger, ref id) = ids[e.index];
+
+        if e.size == 1 {
+            try!(writeln!(w, "{}{}", e.value & 1, id));
+        } else {
+            try!(writeln!(w, "b{:b} {}", e.value, id));
+        }
+
+        if let Some(ref trigger) = *trigger {
+            try!(writeln!(w, "1{}", trigger));
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the short VCD identifier for variable number `index` using
+/// the printable ASCII characters from '!' to '~'
+fn identifier(mut index: usize) -> String {
+    let first = b'!';
+    let base = (b'~' - first + 1) as usize;
+
+    let mut id = String::new();
+
+    loop {
+        id.push((first + (index % base) as u8) as char);
+
+        index /= base;
+
+        if index == 0 {
+            break;
+        }
+    }
+
+    id
+}
+
+fn cycles_to_ns(date: u64) -> u64 {
+    let cpu_freq = ::cpu::CPU_FREQ_HZ as f64;
+
+    (date as f64 * 1_000_000_000. / cpu_freq) as u64
+}
+
+#[test]
+fn identifiers() {
+    assert_eq!(identifier(0), "!");
+    assert_eq!(identifier(1), "\"");
+    assert_eq!(identifier(93), "~");
+    // Least significant "digit" first
+    assert_eq!(identifier(94), "!\"");
+    assert_eq!(identifier(95), "\"\"");
+    assert_eq!(identifier(94 * 94), "!!\"");
+}
+
+#[test]
+fn events_dump() {
+    use tracer::Module;
+
+    let mut gpu = Module { variables: HashMap::new() };
+
+    gpu.event(0, "gp0", 0xe1000000u32);
+    gpu.event(0, "gp0", 0xe1000000u32);
+    gpu.event(100, "gp0", 0xe1000000u32);
+    gpu.trace(100, "vblank", true);
+
+    let mut trace = HashMap::new();
+
+    trace.insert("GPU", gpu);
+
+    let mut out = Vec::new();
+
+    write(&trace, &mut out).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+
+    assert!(lines.contains(&"$var event 1 ! gp0 $end"));
+    assert!(lines.contains(&"$var wire 32 \" gp0_value $end"));
+    assert!(lines.contains(&"$var wire 1 # vblank $end"));
+
+    // Every event triggers, even with the same value
+    assert_eq!(lines.iter().filter(|&&l| l == "1!").count(), 3);
+    assert_eq!(lines.iter().filter(|&&l| l == "1#").count(), 1);
+}
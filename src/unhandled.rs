@@ -0,0 +1,24 @@
+//! Reporting of guest requests the emulator doesn't know how to
+//! handle (unimplemented commands, invalid register accesses...).
+
+/// By default this panics with the given message, which is the
+/// easiest way to notice missing features while working on the
+/// emulator. When the crate is built with the `soft_errors` feature
+/// the message is logged instead and the macro evaluates to
+/// `$fallback`, which can be a value or an early `return`. This lets
+/// the fuzzers (and users who'd rather have a glitch than a crash)
+/// carry on.
+///
+/// ```rustc
+///     let len = unhandled!(1, "Unhandled command {:02x}", cmd);
+/// ```
+macro_rules! unhandled {
+    ($fallback:expr, $($arg:tt)+) => ({
+        if cfg!(feature = "soft_errors") {
+            error!($($arg)+);
+            $fallback
+        } else {
+            panic!($($arg)+)
+        }
+    })
+}